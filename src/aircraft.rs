@@ -329,7 +329,7 @@ impl AeroEffect for Aerodynamics {
 
 /// A turboprop representation of the aircraft's power-plant
 #[allow(dead_code)]
-struct PowerPlant {
+pub(crate) struct PowerPlant {
     /// Name of the power-plant/engine
     name: String,
     /// Maximum shaft-power [W]
@@ -343,7 +343,7 @@ struct PowerPlant {
 impl PowerPlant {
 
     /// Create a PT6 powerplant
-    fn pt6() -> Self {
+    pub(crate) fn pt6() -> Self {
         Self {
             name: "PT6".to_string(),
             shaft_power: 2.0 * 1.12e6,
@@ -351,15 +351,21 @@ impl PowerPlant {
             efficiency: 0.6
         }
     }
+
+    /// Thrust [N] at a throttle setting
+    /// # Arguments
+    /// * `tla` - throttle lever angle, 0 is idle and 1 is full power
+    pub(crate) fn thrust(&self, tla: f64) -> f64 {
+        ((self.shaft_power * self.efficiency) / self.v_max) * tla
+    }
 }
 
 /// Create the AeroEffect for the [PowerPlant] data-class to generate relevant aero forces and torques
 impl AeroEffect for PowerPlant {
 
     fn get_effect(&self, _airstate: AirState, _rates: Vector3, input: &Vec<f64>) -> (Force, Torque) {
-        let thrust = ((self.shaft_power * self.efficiency) / self.v_max) * input[2];
         (
-            Force::body(thrust, 0.0, 0.0),
+            Force::body(self.thrust(input[2]), 0.0, 0.0),
             Torque::body(0.0, 0.0, 0.0)
         )
     }
@@ -374,7 +380,11 @@ pub struct Aircraft {
     // Aircraft controls
     pub controls: HashMap<String, f64>,
     // Path to the aircraft json directory
-    pub data_path: Option<String>
+    pub data_path: Option<String>,
+    // Radius of a sphere enclosing the aircraft [m]
+    pub bounding_radius: f64,
+    // Number of integration steps each call to step is split into
+    pub physics_substeps: usize
 }
 
 impl Aircraft {
@@ -392,6 +402,7 @@ impl Aircraft {
 
         let aero = Aerodynamics::from_json(aircraft_name, path);
         let power = PowerPlant::pt6();
+        let bounding_radius = aero.wing_span / 2.0;
 
         let k_body = Body::new(
             aero.mass,
@@ -419,7 +430,7 @@ impl Aircraft {
                     ])
         };
 
-        Self {name: aircraft_name.to_string(), aff_body, controls, data_path}
+        Self {name: aircraft_name.to_string(), aff_body, controls, data_path, bounding_radius, physics_substeps: 1}
     }
 
     /// Set the controls
@@ -430,21 +441,42 @@ impl Aircraft {
         self.controls = controls;
     }
 
-    /// Step the simulation
+    /// Step the simulation, the controls are held fixed across the `physics_substeps` integration steps
+    /// # Arguments
+    /// * `dt` - time to advance the aircraft by [s]
     #[allow(dead_code)]
     pub fn step(&mut self, dt: f64) {
-        
-
-        // HashMaps aren't ordered so we need to make sure everything comes out in the correct sequence
-        let control_keys = vec!["aileron", "elevator", "tla", "rudder"];
-        let mut control_in: Vec<f64> = Vec::new();
+        let control_in = self.control_input();
+        let substeps = self.physics_substeps.max(1);
+        for _ in 0..substeps {
+            self.aff_body.step(dt / substeps as f64, &control_in);
+        }
+    }
 
-        for key in control_keys {
-            control_in.push(self.controls[key]);
+    /// Force [N] and moment [N.m] from every effector at the current state and controls, summed in the body frame.
+    /// Gravity is not included.
+    pub fn forces(&self) -> (Vector3<f64>, Vector3<f64>) {
+        let control_in = self.control_input();
+        let rates = self.rates();
+        let mut force = Vector3::zeros();
+        let mut moment = Vector3::zeros();
+        for effector in self.aff_body.effectors.iter() {
+            let (f, t) = effector.get_effect(self.aff_body.body.get_airstate(), rates, &control_in);
+            force += match f.frame {
+                Frame::Body => f.force,
+                Frame::World => self.attitude().inverse() * f.force
+            };
+            moment += match t.frame {
+                Frame::Body => t.torque,
+                Frame::World => self.attitude().inverse() * t.torque
+            };
         }
+        (force, moment)
+    }
 
-        // let controls: Vec<_> = self.controls.values().cloned().collect();
-        self.aff_body.step(dt, &control_in); 
+    // Controls in the order the effectors read them, HashMaps aren't ordered so the sequence is fixed here
+    fn control_input(&self) -> Vec<f64> {
+        ["aileron", "elevator", "tla", "rudder"].iter().map(|key| self.controls[*key]).collect()
     }
 
 }
@@ -465,7 +497,9 @@ impl Clone for Aircraft {
             name: ac.name,
             aff_body: ac.aff_body,
             controls: ac.controls,
-            data_path: ac.data_path
+            data_path: ac.data_path,
+            bounding_radius: self.bounding_radius,
+            physics_substeps: self.physics_substeps
         }       
     }
 }
@@ -491,3 +525,85 @@ impl StateView for Aircraft {
         self.aff_body.statevector()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Level flight at 60 m/s nose up pitching at 0.5 rad/s, which sets off the short period oscillation, flown for 3 s
+    fn pitch_oscillation(dt: f64, substeps: usize) -> Aircraft {
+        let mut aircraft = Aircraft::new(
+            "TO",
+            Vector3::new(0.0, 0.0, -1000.0),
+            Vector3::new(60.0, 0.0, 0.0),
+            UnitQuaternion::identity(),
+            Vector3::new(0.0, 0.5, 0.0),
+            None,
+            None
+        );
+        aircraft.physics_substeps = substeps;
+        for _ in 0..(3.0 / dt).round() as usize {
+            aircraft.step(dt);
+        }
+        aircraft
+    }
+
+    // Distance between two aircraft states in position [m] and attitude [rads]
+    fn state_error(a: &Aircraft, b: &Aircraft) -> f64 {
+        (a.position() - b.position()).norm() + a.attitude().angle_to(&b.attitude())
+    }
+
+    #[test]
+    fn more_substeps_reduce_integration_error() {
+        let reference = pitch_oscillation(0.1, 256);
+        let errors: Vec<f64> = [1, 4, 16]
+            .iter()
+            .map(|&substeps| state_error(&pitch_oscillation(0.1, substeps), &reference))
+            .collect();
+        assert!(errors[0] > errors[1] && errors[1] > errors[2], "errors {:?}", errors);
+    }
+
+    #[test]
+    fn substeps_keep_the_control_rate() {
+        // One step split into 4 substeps integrates exactly as 4 quarter steps with the same controls
+        let mut split = pitch_oscillation(0.1, 1);
+        let mut quartered = split.clone();
+        split.physics_substeps = 4;
+        split.step(0.1);
+        for _ in 0..4 {
+            quartered.step(0.1 / 4.0);
+        }
+        assert_eq!(split.position(), quartered.position());
+        assert_eq!(split.attitude(), quartered.attitude());
+    }
+
+    #[test]
+    fn forces_give_the_acceleration() {
+        // Over a short step from rest in rotation the summed forces and moments set the change in velocity and rates
+        let mut aircraft = Aircraft::new(
+            "TO",
+            Vector3::new(0.0, 0.0, -1000.0),
+            Vector3::new(60.0, 0.0, 5.0),
+            UnitQuaternion::from_euler_angles(0.1, 0.05, 0.0),
+            Vector3::zeros(),
+            Some(HashMap::from([
+                ("aileron".to_string(), 0.1),
+                ("elevator".to_string(), -0.05),
+                ("tla".to_string(), 0.5),
+                ("rudder".to_string(), 0.05)
+            ])),
+            None
+        );
+        let aero = Aerodynamics::from_json("TO", None);
+        let (force, moment) = aircraft.forces();
+        let acceleration = aircraft.attitude() * (force / aero.mass) + Vector3::new(0.0, 0.0, 9.81);
+        let angular_acceleration = aero.inertia.try_inverse().unwrap() * moment;
+        assert!(force.norm() > 1.0 && moment.norm() > 1.0);
+
+        let dt = 1e-4;
+        let (velocity, rates) = (aircraft.velocity(), aircraft.rates());
+        aircraft.step(dt);
+        assert!(((aircraft.velocity() - velocity) / dt - acceleration).norm() < 0.01 * acceleration.norm());
+        assert!(((aircraft.rates() - rates) / dt - angular_acceleration).norm() < 0.01 * angular_acceleration.norm());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::aircraft::Aircraft;
+use crate::terrain::StaticObject;
+
+use aerso::types::{StateView, Vector3};
+use glam::Vec2;
+use std::collections::HashMap;
+
+const OBJECT_HEIGHT: f64 = 10.0;  // height of static objects above the ground, enough for the tallest tree [m]
+
+/// Body an aircraft has collided with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collider {
+    /// Another aircraft, index into the world vehicles
+    Aircraft(usize),
+    /// Static object such as a tree, index into the world objects
+    Object(usize)
+}
+
+/// Contact between an aircraft and another body
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct CollisionEvent {
+    /// Index of the aircraft in the world vehicles
+    pub aircraft: usize,
+    /// Body the aircraft hit
+    pub other: Collider,
+    /// Unit vector from the aircraft towards the body it hit, in the world (NED) frame
+    pub normal: Vector3<f64>,
+    /// Closing speed along the normal at contact, zero if the bodies were already separating [m/s]
+    pub impact_speed: f64
+}
+
+/// Uniform grid bucketing indices by cell so only nearby entries need to be compared
+pub struct SpatialGrid {
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>
+}
+
+impl SpatialGrid {
+
+    /// Create an empty grid
+    /// # Arguments
+    /// * `cell_size` - width of each cubic cell [m], usually the largest collision diameter
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new()
+        }
+    }
+
+    /// Add an entry to the cell containing `pos`
+    pub fn insert(&mut self, idx: usize, pos: [f64; 3]) {
+        self.cells.entry(self.key(pos)).or_default().push(idx);
+    }
+
+    /// Entries in every cell overlapping the cube of half-width `radius` around `pos`, these are candidates
+    /// only and still need an exact test
+    pub fn query(&self, pos: [f64; 3], radius: f64) -> impl Iterator<Item = usize> + '_ {
+        let lo = self.key([pos[0] - radius, pos[1] - radius, pos[2] - radius]);
+        let hi = self.key([pos[0] + radius, pos[1] + radius, pos[2] + radius]);
+        (lo[0]..=hi[0])
+            .flat_map(move |x| (lo[1]..=hi[1]).flat_map(move |y| (lo[2]..=hi[2]).map(move |z| [x, y, z])))
+            .filter_map(|key| self.cells.get(&key))
+            .flat_map(|bucket| bucket.iter().copied())
+    }
+
+    fn key(&self, pos: [f64; 3]) -> [i64; 3] {
+        [
+            (pos[0] / self.cell_size).floor() as i64,
+            (pos[1] / self.cell_size).floor() as i64,
+            (pos[2] / self.cell_size).floor() as i64
+        ]
+    }
+}
+
+/// Find every pair of aircraft whose bounding spheres overlap
+/// # Arguments
+/// * `vehicles` - aircraft to check, events refer to them by index
+///
+/// Aircraft are bucketed in a [SpatialGrid] as wide as the largest bounding sphere diameter, so only aircraft in
+/// neighbouring cells are compared and each overlapping pair is reported once.
+pub fn aircraft_collisions(vehicles: &[Aircraft]) -> Vec<CollisionEvent> {
+
+    let cell_size = vehicles
+        .iter()
+        .map(|v| 2.0 * v.bounding_radius)
+        .fold(0.0, f64::max);
+
+    if cell_size <= 0.0 {
+        return vec![];
+    }
+
+    let positions: Vec<[f64; 3]> = vehicles
+        .iter()
+        .map(|v| v.position().into())
+        .collect();
+
+    let mut events = Vec::new();
+    for (idx, other) in candidate_pairs(&positions, cell_size) {
+        let (a, b) = (&vehicles[idx], &vehicles[other]);
+        let separation = b.position() - a.position();
+        if separation.norm() <= a.bounding_radius + b.bounding_radius {
+            // Coincident centres give no direction so fall back to the relative velocity
+            let normal = separation
+                .try_normalize(f64::EPSILON)
+                .or_else(|| (a.velocity() - b.velocity()).try_normalize(f64::EPSILON))
+                .unwrap_or_else(Vector3::z);
+            events.push(CollisionEvent {
+                aircraft: idx,
+                other: Collider::Aircraft(other),
+                normal,
+                impact_speed: closing_speed(a.velocity() - b.velocity(), normal)
+            });
+        }
+    }
+
+    events
+}
+
+/// Find every aircraft touching a static object on the ground
+/// # Arguments
+/// * `vehicles` - aircraft to check, events refer to them by index
+/// * `objects` - static objects on the map, events refer to them by index
+/// * `origin` - map position of the world origin [m]
+/// * `tile_size` - width of a map tile, objects fill the tile they stand on [m]
+///
+/// Objects are upright cylinders `tile_size` wide and `OBJECT_HEIGHT` tall standing on the ground at z = 0.
+/// They are bucketed by tile in a [SpatialGrid] so each aircraft is only compared with objects on the tiles around it.
+pub fn object_collisions(vehicles: &[Aircraft], objects: &[StaticObject], origin: Vec2, tile_size: f32) -> Vec<CollisionEvent> {
+
+    let tile_size = tile_size as f64;
+    let object_radius = tile_size / 2.0;
+
+    // Object centres in the aircraft frame, objects are drawn from their top-left corner
+    let centres: Vec<[f64; 2]> = objects
+        .iter()
+        .map(|object| {
+            let centre = object.pos - origin + tile_size as f32 / 2.0;
+            [centre.x as f64, centre.y as f64]
+        })
+        .collect();
+
+    // Objects all sit in the ground layer of cells, aircraft low enough to hit them query that layer
+    let mut grid = SpatialGrid::new(tile_size);
+    for (idx, centre) in centres.iter().enumerate() {
+        grid.insert(idx, [centre[0], centre[1], 0.0]);
+    }
+
+    let mut events = Vec::new();
+    for (idx, vehicle) in vehicles.iter().enumerate() {
+        let pos = vehicle.position();
+        let reach = vehicle.bounding_radius + object_radius;
+
+        // z is down so altitude is -z
+        if -pos[2] - vehicle.bounding_radius > OBJECT_HEIGHT {
+            continue;
+        }
+
+        for object in grid.query([pos[0], pos[1], 0.0], reach) {
+            let [ox, oy] = centres[object];
+            let offset = Vector3::new(ox - pos[0], oy - pos[1], 0.0);
+            if offset.norm() <= reach {
+                // Above the object the aircraft lands on its top, otherwise it hits the side
+                let normal = if offset.norm() <= object_radius {
+                    Vector3::z()
+                } else {
+                    offset.normalize()
+                };
+                events.push(CollisionEvent {
+                    aircraft: idx,
+                    other: Collider::Object(object),
+                    normal,
+                    impact_speed: closing_speed(vehicle.velocity(), normal)
+                });
+            }
+        }
+    }
+
+    events
+}
+
+/// Pairs of indices (i, j) with i < j whose positions fall in the same or neighbouring cells of a [SpatialGrid]
+/// `cell_size` wide, a superset of the pairs closer than `cell_size`
+/// # Arguments
+/// * `positions` - positions to pair up [m]
+/// * `cell_size` - largest separation that has to be found [m]
+pub fn candidate_pairs(positions: &[[f64; 3]], cell_size: f64) -> Vec<(usize, usize)> {
+
+    let mut grid = SpatialGrid::new(cell_size);
+    for (idx, pos) in positions.iter().enumerate() {
+        grid.insert(idx, *pos);
+    }
+
+    let mut pairs = Vec::new();
+    for (idx, pos) in positions.iter().enumerate() {
+        pairs.extend(grid.query(*pos, cell_size).filter(|&other| other > idx).map(|other| (idx, other)));
+    }
+    pairs
+}
+
+fn closing_speed(relative_velocity: Vector3<f64>, normal: Vector3<f64>) -> f64 {
+    relative_velocity.dot(&normal).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerso::types::UnitQuaternion;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    // Wings-level aircraft at `pos` flying with `vel` in the world frame
+    fn aircraft(pos: [f64; 3], vel: [f64; 3]) -> Aircraft {
+        let heading = vel[1].atan2(vel[0]);
+        let attitude = UnitQuaternion::from_euler_angles(0.0, 0.0, heading);
+        Aircraft::new("TO", Vector3::from(pos), Vector3::from(vel), attitude, Vector3::zeros(), None, None)
+    }
+
+    #[test]
+    fn converging_aircraft_collide_at_contact() {
+        let mut vehicles = vec![
+            aircraft([0.0, 0.0, -500.0], [40.0, 0.0, 0.0]),
+            aircraft([200.0, 0.0, -500.0], [-40.0, 0.0, 0.0])
+        ];
+        let contact = vehicles[0].bounding_radius + vehicles[1].bounding_radius;
+
+        let dt = 0.01;
+        let mut previous_separation = f64::INFINITY;
+        for _ in 0..1000 {
+            let separation = (vehicles[1].position() - vehicles[0].position()).norm();
+            let events = aircraft_collisions(&vehicles);
+            if !events.is_empty() {
+                // Fires on the first step the spheres touch, not before and not a step late
+                assert!(separation <= contact && previous_separation > contact);
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].aircraft, 0);
+                assert_eq!(events[0].other, Collider::Aircraft(1));
+                return;
+            }
+            assert!(separation > contact, "missed contact at {} m", separation);
+            previous_separation = separation;
+            for vehicle in vehicles.iter_mut() {
+                vehicle.step(dt);
+            }
+        }
+        panic!("aircraft never met");
+    }
+
+    #[test]
+    fn cloned_aircraft_keeps_its_bounding_radius() {
+        let mut vehicle = aircraft([0.0, 0.0, -500.0], [40.0, 0.0, 0.0]);
+        vehicle.bounding_radius = 20.0;
+        assert_eq!(vehicle.clone().bounding_radius, 20.0);
+    }
+
+    // Tree whose centre is at `centre` in the aircraft frame on a map with 25 m tiles
+    fn tree(centre: [f32; 2], origin: Vec2) -> StaticObject {
+        StaticObject {
+            name: "Tree".to_string(),
+            asset: "tree".to_string(),
+            pos: Vec2::from(centre) + origin - 12.5
+        }
+    }
+
+    #[test]
+    fn flying_into_a_tree_collides() {
+        let origin = Vec2::splat(500.0);
+        let objects = vec![tree([-100.0, 100.0], origin), tree([100.0, 0.0], origin)];
+        let mut vehicles = vec![aircraft([0.0, 0.0, -5.0], [40.0, 0.0, 0.0])];
+        let reach = vehicles[0].bounding_radius + 12.5;
+
+        for _ in 0..1000 {
+            let events = object_collisions(&vehicles, &objects, origin, 25.0);
+            let pos = vehicles[0].position();
+            let distance = (pos[0] - 100.0).hypot(pos[1]);
+            if !events.is_empty() {
+                assert!(distance <= reach);
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].other, Collider::Object(1));
+                // Hits the side of the trunk head on
+                assert!((events[0].normal - Vector3::x()).norm() < 0.1, "normal {:?}", events[0].normal);
+                return;
+            }
+            assert!(distance > reach, "missed the tree at {} m", distance);
+            vehicles[0].step(0.01);
+        }
+        panic!("aircraft never reached the tree");
+    }
+
+    #[test]
+    fn flying_over_a_tree_is_clear() {
+        let origin = Vec2::splat(500.0);
+        let objects = vec![tree([0.0, 0.0], origin)];
+        let vehicles = vec![aircraft([0.0, 0.0, -50.0], [40.0, 0.0, 0.0])];
+        assert!(object_collisions(&vehicles, &objects, origin, 25.0).is_empty());
+    }
+
+    #[test]
+    fn grid_matches_brute_force_with_fewer_pairs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let vehicles: Vec<Aircraft> = (0..1000)
+            .map(|_| aircraft([rng.gen_range(0.0..300.0), rng.gen_range(0.0..300.0), rng.gen_range(-300.0..0.0)], [40.0, 0.0, 0.0]))
+            .collect();
+
+        let mut brute_force = Vec::new();
+        for a in 0..vehicles.len() {
+            for b in a + 1..vehicles.len() {
+                let separation = (vehicles[b].position() - vehicles[a].position()).norm();
+                if separation <= vehicles[a].bounding_radius + vehicles[b].bounding_radius {
+                    brute_force.push((a, b));
+                }
+            }
+        }
+
+        let mut found: Vec<(usize, usize)> = aircraft_collisions(&vehicles)
+            .iter()
+            .map(|event| match event.other {
+                Collider::Aircraft(other) => (event.aircraft, other),
+                Collider::Object(_) => panic!("no objects in the world")
+            })
+            .collect();
+        found.sort();
+        assert!(!brute_force.is_empty());
+        assert_eq!(found, brute_force);
+
+        // The grid compares only a small fraction of the 499500 pairs brute force visits
+        let positions: Vec<[f64; 3]> = vehicles.iter().map(|v| v.position().into()).collect();
+        let visited = candidate_pairs(&positions, 2.0 * vehicles[0].bounding_radius).len();
+        assert!(visited * 100 < 1000 * 999 / 2, "visited {} pairs", visited);
+    }
+
+    #[test]
+    fn impact_speed_is_relative_velocity_along_normal() {
+        let vehicles = vec![
+            aircraft([0.0, 0.0, -500.0], [30.0, 10.0, 0.0]),
+            aircraft([3.0, 4.0, -500.0], [-10.0, 5.0, 2.0])
+        ];
+        let events = aircraft_collisions(&vehicles);
+        assert_eq!(events.len(), 1);
+
+        // Normal points from the first aircraft to the second, (40, 5, -2) . (0.6, 0.8, 0) = 28 m/s
+        assert!((events[0].normal - Vector3::new(0.6, 0.8, 0.0)).norm() < 1e-9);
+        let relative = vehicles[0].velocity() - vehicles[1].velocity();
+        assert!((events[0].impact_speed - relative.dot(&events[0].normal)).abs() < 1e-9);
+        assert!((events[0].impact_speed - 28.0).abs() < 1e-9);
+
+        // Touching but moving apart is a contact without an impact
+        let separating = vec![
+            aircraft([0.0, 0.0, -500.0], [-30.0, 0.0, 0.0]),
+            aircraft([3.0, 4.0, -500.0], [30.0, 0.0, 0.0])
+        ];
+        assert_eq!(aircraft_collisions(&separating)[0].impact_speed, 0.0);
+    }
+}
@@ -0,0 +1,137 @@
+use aerso::types::{Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+
+const WGS84_A: f64 = 6378137.0;  // semi-major axis [m]
+const WGS84_E2: f64 = 6.69437999014e-3;  // first eccentricity squared
+const LATITUDE_ITERATIONS: usize = 10;  // upper bound on the fixed-point iterations for latitude from ECEF
+const LATITUDE_TOLERANCE: f64 = 1e-14;  // change in latitude that ends the iteration early [rads]
+
+/// Position on the WGS84 ellipsoid
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Geodetic {
+    pub lat: f64,  // latitude [deg]
+    pub lon: f64,  // longitude [deg]
+    pub alt: f64  // height above the ellipsoid [m]
+}
+
+impl Geodetic {
+
+    /// Geodetic position of a point in the local frame with this origin
+    /// # Arguments
+    /// * `pos` - local position [m] in the simulation's north-east-down frame
+    ///
+    /// The local frame is the tangent plane at the origin, converted through Earth-centred Earth-fixed (ECEF)
+    /// coordinates so the Earth's curvature is included. Flying level in the local frame climbs above the ellipsoid,
+    /// by about 7.9 m after 10 km. This holds at any distance and at the poles.
+    pub fn local_to_geodetic(&self, pos: Vector3<f64>) -> Geodetic {
+        let ecef = self.ecef() + self.ned_to_ecef() * pos;
+        Geodetic::from_ecef(ecef)
+    }
+
+    /// Local north-east-down position [m] of a geodetic point relative to this origin, inverse of
+    /// [Geodetic::local_to_geodetic]
+    /// # Arguments
+    /// * `point` - geodetic position to convert
+    #[allow(dead_code)]
+    pub fn geodetic_to_local(&self, point: &Geodetic) -> Vector3<f64> {
+        self.ned_to_ecef().transpose() * (point.ecef() - self.ecef())
+    }
+
+    // Earth-centred Earth-fixed position [m]
+    fn ecef(&self) -> Vector3<f64> {
+        let (sin_lat, cos_lat) = self.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.lon.to_radians().sin_cos();
+        let r_normal = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+        Vector3::new(
+            (r_normal + self.alt) * cos_lat * cos_lon,
+            (r_normal + self.alt) * cos_lat * sin_lon,
+            (r_normal * (1.0 - WGS84_E2) + self.alt) * sin_lat
+        )
+    }
+
+    // Geodetic position of an ECEF point [m], latitude is found by fixed-point iteration
+    fn from_ecef(ecef: Vector3<f64>) -> Geodetic {
+        let p = ecef[0].hypot(ecef[1]);
+        let lon = ecef[1].atan2(ecef[0]);
+
+        let mut lat = ecef[2].atan2(p * (1.0 - WGS84_E2));
+        for _ in 0..LATITUDE_ITERATIONS {
+            let sin_lat = lat.sin();
+            let r_normal = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+            let next = (ecef[2] + WGS84_E2 * r_normal * sin_lat).atan2(p);
+            let converged = (next - lat).abs() < LATITUDE_TOLERANCE;
+            lat = next;
+            if converged {
+                break;
+            }
+        }
+
+        // Valid at every latitude, unlike p / cos(lat) - N which divides by zero at the poles
+        let (sin_lat, cos_lat) = lat.sin_cos();
+        let alt = p * cos_lat + ecef[2] * sin_lat - WGS84_A * (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+
+        Geodetic { lat: lat.to_degrees(), lon: lon.to_degrees(), alt }
+    }
+
+    // Rotation from the north-east-down frame at this point to ECEF, its columns are north, east and down
+    fn ned_to_ecef(&self) -> Matrix3<f64> {
+        let (sin_lat, cos_lat) = self.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.lon.to_radians().sin_cos();
+        Matrix3::new(
+            -sin_lat * cos_lon, -sin_lon, -cos_lat * cos_lon,
+            -sin_lat * sin_lon, cos_lon, -cos_lat * sin_lon,
+            cos_lat, 0.0, -sin_lat
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(lat: f64, lon: f64, alt: f64) -> Geodetic {
+        Geodetic { lat, lon, alt }
+    }
+
+    #[test]
+    fn local_round_trip() {
+        let origins = [origin(0.0, 0.0, 0.0), origin(51.5, -0.1, 120.0), origin(-33.9, 151.2, 2000.0), origin(89.99, 45.0, 0.0)];
+        let offsets = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(100.0, -250.0, -50.0),
+            Vector3::new(10000.0, 10000.0, -3000.0),
+            Vector3::new(-200000.0, 150000.0, -10000.0)
+        ];
+        for origin in origins.iter() {
+            for offset in offsets.iter() {
+                let back = origin.geodetic_to_local(&origin.local_to_geodetic(*offset));
+                assert!((back - offset).norm() < 1e-6, "{:?} returned as {:?} from {:?}", offset, back, origin);
+            }
+        }
+    }
+
+    #[test]
+    fn level_flight_climbs_above_curved_earth() {
+        // Over 10 km the ellipsoid drops away by d^2 / 2M with meridian radius M = a (1 - e^2) at the equator
+        let point = origin(0.0, 0.0, 0.0).local_to_geodetic(Vector3::new(10000.0, 0.0, 0.0));
+        let drop = 10000.0_f64.powi(2) / (2.0 * WGS84_A * (1.0 - WGS84_E2));
+        assert!((point.alt - drop).abs() < 0.01, "altitude {} m, expected {} m", point.alt, drop);
+
+        // A point a degree east along the equator is a chord below the tangent plane
+        let local = origin(0.0, 0.0, 0.0).geodetic_to_local(&origin(0.0, 1.0, 0.0));
+        let angle = 1.0_f64.to_radians();
+        assert!((local - Vector3::new(0.0, WGS84_A * angle.sin(), WGS84_A * (1.0 - angle.cos()))).norm() < 1e-6);
+    }
+
+    #[test]
+    fn poles_are_finite() {
+        let pole = origin(90.0, 0.0, 100.0);
+        let point = pole.local_to_geodetic(Vector3::new(1000.0, 0.0, -50.0));
+        assert!(point.lat.is_finite() && point.lon.is_finite() && point.alt.is_finite());
+        assert!((pole.local_to_geodetic(Vector3::zeros()).alt - 100.0).abs() < 1e-6);
+        assert!((pole.local_to_geodetic(Vector3::zeros()).lat - 90.0).abs() < 1e-9);
+
+        let back = pole.geodetic_to_local(&point);
+        assert!((back - Vector3::new(1000.0, 0.0, -50.0)).norm() < 1e-6);
+    }
+}
@@ -3,9 +3,19 @@ mod aircraft;
 mod world;
 mod trim;
 mod runway;
+mod planner;
+mod collision;
+mod telemetry;
+mod geodetic;
+mod utils;
 
-pub use terrain::{Terrain, TerrainConfig, Tile, RandomFuncs, StaticObject};
+pub use terrain::{Terrain, TerrainConfig, Tile, RandomFuncs, RandomState, StaticObject};
 pub use aircraft::Aircraft;
 pub use world::{World, Camera, Settings};
-pub use trim::Trim;
-pub use runway::Runway;
+pub use trim::{Trim, TrimError};
+pub use runway::{Runway, RunwayRegion, RunwayPosition};
+pub use planner::{WaypointPlan, DubinsPath, Pose, Segment, SegmentType};
+pub use collision::{CollisionEvent, Collider, SpatialGrid};
+pub use telemetry::Telemetry;
+pub use geodetic::Geodetic;
+pub use utils::{quat_to_euler, euler_to_quat, quat_to_dcm};
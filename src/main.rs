@@ -2,6 +2,10 @@ mod world;
 mod aircraft;
 mod terrain;
 mod runway;
+mod collision;
+mod telemetry;
+mod geodetic;
+mod utils;
 use world::World;
 
 use glam::Vec2;
@@ -0,0 +1,421 @@
+use glam::{Vec2, Vec3};
+use std::f32::consts::PI;
+
+/// Direction of a Dubins path segment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SegmentType {
+    /// Counter-clockwise turn at the minimum turn radius
+    Left,
+    /// Straight line
+    Straight,
+    /// Clockwise turn at the minimum turn radius
+    Right
+}
+
+/// Single turn or straight section of a Dubins path
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    /// Turn direction or straight
+    pub kind: SegmentType,
+    /// Length of the segment along the ground [m]
+    pub length: f32
+}
+
+/// Pose of the aircraft used by the planner, heading is measured anti-clockwise from the x-axis [rads]
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    /// Position [m], z is altitude
+    pub pos: Vec3,
+    /// Heading [rads]
+    pub heading: f32
+}
+
+/// Shortest Dubins path between two poses
+#[derive(Clone, Debug)]
+pub struct DubinsPath {
+    /// Pose at the start of the path
+    pub start: Pose,
+    /// Turn radius used to build the path [m]
+    pub radius: f32,
+    /// Segments making up the path, in order
+    pub segments: Vec<Segment>,
+    /// Altitude at the end of the path [m]
+    pub end_alt: f32
+}
+
+impl DubinsPath {
+
+    /// Find the shortest of the six Dubins words joining two poses
+    /// # Arguments
+    /// * `start` - pose to leave from
+    /// * `end` - pose to arrive at
+    /// * `radius` - minimum turn radius [m]
+    ///
+    /// # Panics
+    /// If `radius` is not positive, the path lengths are normalised by it
+    pub fn shortest(start: Pose, end: Pose, radius: f32) -> Self {
+
+        assert!(radius > 0.0, "Dubins turn radius must be positive, got {}", radius);
+
+        let delta = end.pos.truncate() - start.pos.truncate();
+        let d = delta.length() / radius;
+        let theta = mod2pi(delta.y.atan2(delta.x));
+        let alpha = mod2pi(start.heading - theta);
+        let beta = mod2pi(end.heading - theta);
+
+        let candidates = [
+            ([SegmentType::Left, SegmentType::Straight, SegmentType::Left], lsl(alpha, beta, d)),
+            ([SegmentType::Right, SegmentType::Straight, SegmentType::Right], rsr(alpha, beta, d)),
+            ([SegmentType::Left, SegmentType::Straight, SegmentType::Right], lsr(alpha, beta, d)),
+            ([SegmentType::Right, SegmentType::Straight, SegmentType::Left], rsl(alpha, beta, d)),
+            ([SegmentType::Right, SegmentType::Left, SegmentType::Right], rlr(alpha, beta, d)),
+            ([SegmentType::Left, SegmentType::Right, SegmentType::Left], lrl(alpha, beta, d))
+        ];
+
+        // LSL and RSR always exist so there is always a feasible word, the CCC words cover waypoints closer than the turn diameter
+        let (kinds, params) = candidates
+            .iter()
+            .filter_map(|(kinds, params)| params.map(|p| (kinds, p)))
+            .min_by(|a, b| (a.1.iter().sum::<f32>()).total_cmp(&b.1.iter().sum::<f32>()))
+            .unwrap();
+
+        let segments = kinds
+            .iter()
+            .zip(params.iter())
+            .map(|(kind, length)| Segment { kind: *kind, length: length * radius })
+            .collect();
+
+        Self {
+            start,
+            radius,
+            segments,
+            end_alt: end.pos.z
+        }
+    }
+
+    /// Total length of the path along the ground [m]
+    pub fn length(&self) -> f32 {
+        self.segments.iter().map(|s| s.length).sum()
+    }
+
+    /// Word describing the path, e.g. "LSR"
+    pub fn word(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| match s.kind {
+                SegmentType::Left => 'L',
+                SegmentType::Straight => 'S',
+                SegmentType::Right => 'R'
+            })
+            .collect()
+    }
+
+    /// Pose a distance `s` [m] along the path, altitude is interpolated linearly with distance
+    pub fn sample(&self, s: f32) -> Pose {
+
+        let total = self.length();
+        let s = s.clamp(0.0, total);
+
+        let mut pos = self.start.pos.truncate();
+        let mut heading = self.start.heading;
+        let mut remaining = s;
+
+        for segment in self.segments.iter() {
+            let step = remaining.min(segment.length);
+            (pos, heading) = segment_end(pos, heading, segment.kind, step, self.radius);
+            remaining -= step;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+
+        let alt = if total > 0.0 {
+            self.start.pos.z + (self.end_alt - self.start.pos.z) * (s / total)
+        } else {
+            self.end_alt
+        };
+
+        Pose {
+            pos: pos.extend(alt),
+            heading: mod2pi(heading)
+        }
+    }
+}
+
+/// Dubins path through an ordered list of waypoints
+#[derive(Clone, Debug)]
+pub struct WaypointPlan {
+    /// Paths joining each consecutive pair of waypoints
+    pub legs: Vec<DubinsPath>
+}
+
+impl WaypointPlan {
+
+    /// Plan a path from the start pose through each waypoint in turn
+    /// # Arguments
+    /// * `start` - initial pose of the aircraft
+    /// * `waypoints` - ordered waypoints [m], z is altitude
+    /// * `radius` - minimum turn radius [m]
+    ///
+    /// The heading at each waypoint points at the following waypoint, the final waypoint keeps the heading of
+    /// the straight line from the previous waypoint. Waypoints coincident with the previous one are dropped.
+    pub fn new(start: Pose, waypoints: &[Vec3], radius: f32) -> Self {
+
+        let mut points: Vec<Vec3> = Vec::new();
+        let mut previous = start.pos;
+        for waypoint in waypoints {
+            if (waypoint.truncate() - previous.truncate()).length() > f32::EPSILON {
+                points.push(*waypoint);
+                previous = *waypoint;
+            }
+        }
+
+        let mut legs = Vec::new();
+        let mut pose = start;
+        for (idx, point) in points.iter().enumerate() {
+            let towards = match points.get(idx + 1) {
+                Some(next) => next.truncate() - point.truncate(),
+                None => point.truncate() - pose.pos.truncate()
+            };
+            let end = Pose {
+                pos: *point,
+                heading: towards.y.atan2(towards.x)
+            };
+            legs.push(DubinsPath::shortest(pose, end, radius));
+            pose = end;
+        }
+
+        Self { legs }
+    }
+
+    /// Total length of the plan along the ground [m]
+    pub fn length(&self) -> f32 {
+        self.legs.iter().map(|l| l.length()).sum()
+    }
+
+    /// Pose a distance `s` [m] along the whole plan, returns None when the plan is empty
+    pub fn sample(&self, s: f32) -> Option<Pose> {
+        let mut remaining = s.max(0.0);
+        for leg in self.legs.iter() {
+            let length = leg.length();
+            if remaining <= length {
+                return Some(leg.sample(remaining));
+            }
+            remaining -= length;
+        }
+        self.legs.last().map(|leg| leg.sample(leg.length()))
+    }
+}
+
+fn segment_end(pos: Vec2, heading: f32, kind: SegmentType, length: f32, radius: f32) -> (Vec2, f32) {
+    let turn = length / radius;
+    match kind {
+        SegmentType::Left => (
+            pos + radius * Vec2::new((heading + turn).sin() - heading.sin(), heading.cos() - (heading + turn).cos()),
+            heading + turn
+        ),
+        SegmentType::Right => (
+            pos + radius * Vec2::new(heading.sin() - (heading - turn).sin(), (heading - turn).cos() - heading.cos()),
+            heading - turn
+        ),
+        SegmentType::Straight => (
+            pos + length * Vec2::from_angle(heading),
+            heading
+        )
+    }
+}
+
+fn mod2pi(theta: f32) -> f32 {
+    theta.rem_euclid(2.0 * PI)
+}
+
+// Normalised segment lengths for each word, following Shkel & Lumelsky (2001)
+
+fn lsl(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let p_sq = 2.0 + d * d - 2.0 * (alpha - beta).cos() + 2.0 * d * (sa - sb);
+    if p_sq < 0.0 {
+        return None;
+    }
+    let tmp = (cb - ca).atan2(d + sa - sb);
+    Some([mod2pi(tmp - alpha), p_sq.sqrt(), mod2pi(beta - tmp)])
+}
+
+fn rsr(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let p_sq = 2.0 + d * d - 2.0 * (alpha - beta).cos() + 2.0 * d * (sb - sa);
+    if p_sq < 0.0 {
+        return None;
+    }
+    let tmp = (ca - cb).atan2(d - sa + sb);
+    Some([mod2pi(alpha - tmp), p_sq.sqrt(), mod2pi(tmp - beta)])
+}
+
+fn lsr(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let p_sq = -2.0 + d * d + 2.0 * (alpha - beta).cos() + 2.0 * d * (sa + sb);
+    if p_sq < 0.0 {
+        return None;
+    }
+    let p = p_sq.sqrt();
+    let tmp = (-ca - cb).atan2(d + sa + sb) - (-2.0_f32).atan2(p);
+    Some([mod2pi(tmp - alpha), p, mod2pi(tmp - beta)])
+}
+
+fn rsl(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let p_sq = -2.0 + d * d + 2.0 * (alpha - beta).cos() - 2.0 * d * (sa + sb);
+    if p_sq < 0.0 {
+        return None;
+    }
+    let p = p_sq.sqrt();
+    let tmp = (ca + cb).atan2(d - sa - sb) - (2.0_f32).atan2(p);
+    Some([mod2pi(alpha - tmp), p, mod2pi(beta - tmp)])
+}
+
+fn rlr(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let tmp = (6.0 - d * d + 2.0 * (alpha - beta).cos() + 2.0 * d * (sa - sb)) / 8.0;
+    if tmp.abs() > 1.0 {
+        return None;
+    }
+    let phi = (ca - cb).atan2(d - sa + sb);
+    let p = mod2pi(2.0 * PI - tmp.acos());
+    let t = mod2pi(alpha - phi + mod2pi(p / 2.0));
+    Some([t, p, mod2pi(alpha - beta - t + p)])
+}
+
+fn lrl(alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+    let tmp = (6.0 - d * d + 2.0 * (alpha - beta).cos() + 2.0 * d * (sb - sa)) / 8.0;
+    if tmp.abs() > 1.0 {
+        return None;
+    }
+    let phi = (ca - cb).atan2(d + sa - sb);
+    let p = mod2pi(2.0 * PI - tmp.acos());
+    let t = mod2pi(-alpha - phi + p / 2.0);
+    Some([t, p, mod2pi(beta - alpha - t + p)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(x: f32, y: f32, heading: f32) -> Pose {
+        Pose { pos: Vec3::new(x, y, 0.0), heading }
+    }
+
+    // Shortest path between the poses, checking it ends on the target pose
+    fn solve(start: Pose, end: Pose, radius: f32) -> DubinsPath {
+        let path = DubinsPath::shortest(start, end, radius);
+        let reached = path.sample(path.length());
+        assert!((reached.pos - end.pos).length() < 1e-2, "ended at {:?}", reached.pos);
+        let heading_error = mod2pi(reached.heading - end.heading);
+        assert!(heading_error.min(2.0 * PI - heading_error) < 1e-3, "ended heading {}", reached.heading);
+        path
+    }
+
+    #[test]
+    fn selects_lsl() {
+        let path = solve(pose(0.0, 0.0, 0.0), pose(0.0, 100.0, PI), 10.0);
+        assert_eq!(path.word(), "LSL");
+        assert!((path.length() - (80.0 + 10.0 * PI)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn selects_rsr() {
+        let path = solve(pose(0.0, 0.0, 0.0), pose(0.0, -100.0, PI), 10.0);
+        assert_eq!(path.word(), "RSR");
+        assert!((path.length() - (80.0 + 10.0 * PI)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn selects_lsr() {
+        let path = solve(pose(0.0, 0.0, 0.0), pose(100.0, 50.0, -PI / 2.0), 10.0);
+        assert_eq!(path.word(), "LSR");
+    }
+
+    #[test]
+    fn selects_rsl() {
+        let path = solve(pose(0.0, 0.0, 0.0), pose(100.0, -50.0, PI / 2.0), 10.0);
+        assert_eq!(path.word(), "RSL");
+    }
+
+    #[test]
+    #[should_panic(expected = "radius must be positive")]
+    fn rejects_zero_radius() {
+        DubinsPath::shortest(pose(0.0, 0.0, 0.0), pose(100.0, 0.0, 0.0), 0.0);
+    }
+
+    // Plan through the waypoints, checking each leg ends on its waypoint, heading for the next distinct one, and
+    // that the whole plan samples through every waypoint in turn
+    fn plan(start: Pose, waypoints: &[Vec3], radius: f32) -> WaypointPlan {
+        let plan = WaypointPlan::new(start, waypoints, radius);
+        let mut distinct: Vec<Vec3> = Vec::new();
+        for waypoint in waypoints {
+            let previous = distinct.last().copied().unwrap_or(start.pos);
+            if (*waypoint - previous).truncate().length() > f32::EPSILON {
+                distinct.push(*waypoint);
+            }
+        }
+        assert_eq!(plan.legs.len(), distinct.len());
+
+        let mut travelled = 0.0;
+        for (idx, (leg, waypoint)) in plan.legs.iter().zip(distinct.iter()).enumerate() {
+            let towards = match distinct.get(idx + 1) {
+                Some(next) => *next - *waypoint,
+                None => *waypoint - if idx > 0 { distinct[idx - 1] } else { start.pos }
+            };
+            let reached = leg.sample(leg.length());
+            assert!((reached.pos - *waypoint).length() < 1e-2, "leg {} ended at {:?}", idx, reached.pos);
+            let heading_error = mod2pi(reached.heading - towards.y.atan2(towards.x));
+            assert!(heading_error.min(2.0 * PI - heading_error) < 1e-3, "leg {} ended heading {}", idx, reached.heading);
+
+            travelled += leg.length();
+            assert!((plan.sample(travelled).unwrap().pos - *waypoint).length() < 1e-2);
+        }
+        plan
+    }
+
+    #[test]
+    fn plans_through_waypoints() {
+        let waypoints = [
+            Vec3::new(300.0, 0.0, 50.0),
+            Vec3::new(300.0, 300.0, 100.0),
+            Vec3::new(0.0, 300.0, 0.0),
+            Vec3::new(0.0, 0.0, 20.0)
+        ];
+        let plan = plan(pose(0.0, 0.0, 0.0), &waypoints, 20.0);
+        assert!((plan.length() - plan.legs.iter().map(|leg| leg.length()).sum::<f32>()).abs() < 1e-3);
+        assert!(WaypointPlan::new(pose(0.0, 0.0, 0.0), &[], 20.0).sample(0.0).is_none());
+    }
+
+    #[test]
+    fn drops_coincident_waypoints() {
+        let waypoints = [
+            Vec3::new(200.0, 0.0, 0.0),
+            Vec3::new(200.0, 0.0, 0.0),
+            Vec3::new(200.0, 200.0, 0.0)
+        ];
+        assert_eq!(plan(pose(0.0, 0.0, 0.0), &waypoints, 20.0).legs.len(), 2);
+
+        // A first waypoint on the start position is dropped too
+        let waypoints = [Vec3::ZERO, Vec3::new(200.0, 0.0, 0.0)];
+        assert_eq!(plan(pose(0.0, 0.0, 0.0), &waypoints, 20.0).legs.len(), 1);
+    }
+
+    #[test]
+    fn short_leg_uses_three_turns() {
+        // The leg from (300, 0) to (310, 0) is half a turn radius long and has to reverse heading towards (100, 0)
+        let waypoints = [
+            Vec3::new(300.0, 0.0, 50.0),
+            Vec3::new(300.0, 0.0, 50.0),
+            Vec3::new(310.0, 0.0, 60.0),
+            Vec3::new(100.0, 0.0, 80.0),
+            Vec3::new(100.0, 200.0, 100.0)
+        ];
+        let plan = plan(pose(0.0, 0.0, 0.0), &waypoints, 20.0);
+        assert_eq!(plan.legs.len(), 4);
+        assert!(["RLR", "LRL"].contains(&plan.legs[1].word().as_str()), "short leg flew {}", plan.legs[1].word());
+    }
+}
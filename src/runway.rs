@@ -7,7 +7,28 @@ pub struct Runway {
     pub asset: String,
     pub pos: Vec2,  // center point of runway [m]
     pub dims: Vec2,  // dimensions of runway [m]
-    pub heading: f32 // heading of runway [m]
+    pub heading: f32, // heading of runway [m]
+    pub corridor_width: f32,  // width of the approach corridor along the extended centerline [m]
+    pub corridor_length: f32  // length of the approach corridor beyond each end of the runway [m]
+}
+
+/// Part of the airfield a point lies in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunwayRegion {
+    /// On the runway surface
+    OnRunway,
+    /// Past either end of the runway inside the corridor along the extended centerline
+    Corridor,
+    /// Anywhere else
+    Off
+}
+
+/// Position of a point relative to the runway
+#[derive(Clone, Copy, Debug)]
+pub struct RunwayPosition {
+    pub region: RunwayRegion,
+    pub lateral: f32,  // offset from the centerline, positive to the right looking along the heading [m]
+    pub longitudinal: f32  // distance along the heading from the center of the runway [m]
 }
 
 impl Default for Runway {
@@ -18,7 +39,9 @@ impl Default for Runway {
             asset: "runway".to_string(),
             pos: Vec2::new(0.0, 0.0),
             dims: Vec2::new(25.0, 1000.0),
-            heading: 0.0
+            heading: 0.0,
+            corridor_width: 150.0,
+            corridor_length: 15000.0
         }
     }
 }
@@ -44,6 +67,27 @@ impl Runway {
 
     }
 
+    /// Locate a point relative to the runway
+    /// # Arguments
+    /// * `test_point` - position to locate [m]
+    pub fn locate(&self, test_point: Vec2) -> RunwayPosition {
+
+        let local = Vec2::from_angle(-self.heading.to_radians()).rotate(test_point - self.pos);
+        let (longitudinal, lateral) = (local[0], local[1]);
+
+        // The corridor only extends beyond the ends, beside the runway is off
+        let beyond_end = longitudinal.abs() - self.dims[1] / 2.0;
+        let region = if beyond_end <= 0.0 && lateral.abs() <= self.dims[0] / 2.0 {
+            RunwayRegion::OnRunway
+        } else if 0.0 < beyond_end && beyond_end <= self.corridor_length && lateral.abs() <= self.corridor_width / 2.0 {
+            RunwayRegion::Corridor
+        } else {
+            RunwayRegion::Off
+        };
+
+        RunwayPosition { region, lateral, longitudinal }
+    }
+
     pub fn approach_points(&self) -> HashMap<String, Vec<f32>> {
         
         let touchdown_fraction = 0.8;  // fraction of runway that is landable, used to determine touchdown point
@@ -107,3 +151,61 @@ fn is_point_inside_polygon(point: Vec2, polygon_points: Vec<Vec2>) -> bool {
 
     inside
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Default 25 x 1000 m runway at the origin, turned to `heading` [deg]
+    fn runway(heading: f32) -> Runway {
+        Runway { heading, ..Runway::default() }
+    }
+
+    #[test]
+    fn centerline_is_on_runway() {
+        let located = runway(0.0).locate(Vec2::new(100.0, 0.0));
+        assert_eq!(located.region, RunwayRegion::OnRunway);
+        assert_eq!(located.lateral, 0.0);
+        assert_eq!(located.longitudinal, 100.0);
+    }
+
+    #[test]
+    fn threshold_separates_runway_from_corridor() {
+        let runway = runway(0.0);
+        assert_eq!(runway.locate(Vec2::new(500.0, 0.0)).region, RunwayRegion::OnRunway);
+        assert_eq!(runway.locate(Vec2::new(-500.0, 0.0)).region, RunwayRegion::OnRunway);
+        assert_eq!(runway.locate(Vec2::new(501.0, 0.0)).region, RunwayRegion::Corridor);
+        assert_eq!(runway.locate(Vec2::new(-501.0, 70.0)).region, RunwayRegion::Corridor);
+        assert_eq!(runway.locate(Vec2::new(500.0 + 15000.0, 0.0)).region, RunwayRegion::Corridor);
+    }
+
+    #[test]
+    fn edge_is_on_runway() {
+        let runway = runway(0.0);
+        let right = runway.locate(Vec2::new(0.0, 12.5));
+        assert_eq!(right.region, RunwayRegion::OnRunway);
+        assert_eq!(right.lateral, 12.5);
+        assert_eq!(runway.locate(Vec2::new(0.0, -12.5)).region, RunwayRegion::OnRunway);
+
+        // Beside the runway is off even inside the corridor width, the corridor only extends past the ends
+        assert_eq!(runway.locate(Vec2::new(0.0, 13.0)).region, RunwayRegion::Off);
+    }
+
+    #[test]
+    fn outside_is_off() {
+        let runway = runway(0.0);
+        assert_eq!(runway.locate(Vec2::new(600.0, 76.0)).region, RunwayRegion::Off);
+        assert_eq!(runway.locate(Vec2::new(500.0 + 15001.0, 0.0)).region, RunwayRegion::Off);
+        assert_eq!(runway.locate(Vec2::new(0.0, 1000.0)).region, RunwayRegion::Off);
+    }
+
+    #[test]
+    fn locate_follows_heading() {
+        // Runway pointing east, north of it is to the left looking along the heading
+        let located = runway(90.0).locate(Vec2::new(10.0, 400.0));
+        assert_eq!(located.region, RunwayRegion::OnRunway);
+        assert!((located.longitudinal - 400.0).abs() < 1e-3);
+        assert!((located.lateral + 10.0).abs() < 1e-3);
+        assert_eq!(runway(90.0).locate(Vec2::new(400.0, 0.0)).region, RunwayRegion::Off);
+    }
+}
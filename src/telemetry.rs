@@ -0,0 +1,135 @@
+use crate::aircraft::Aircraft;
+use crate::utils::quat_to_euler;
+
+use aerso::types::StateView;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const CONTROL_KEYS: [&str; 4] = ["aileron", "elevator", "tla", "rudder"];
+
+/// Writes the state of aircraft to a CSV file, one row per call to [Telemetry::record]
+pub struct Telemetry {
+    writer: BufWriter<File>
+}
+
+impl Telemetry {
+
+    /// Create the CSV file and write its header, an existing file is overwritten
+    /// # Arguments
+    /// * `path` - location of the CSV file
+    pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", Self::header().join(","))?;
+        Ok(Self { writer })
+    }
+
+    /// Column names, `id` is the aircraft's index in the world, position and velocity are in the world (NED) frame,
+    /// rates and the summed aerodynamic and thrust forces [N] and moments [N.m] in the body frame
+    pub fn header() -> Vec<String> {
+        let mut header: Vec<String> = [
+            "time", "id", "x", "y", "z", "vn", "ve", "vd", "roll", "pitch", "yaw", "p", "q", "r",
+            "fx", "fy", "fz", "mx", "my", "mz"
+        ].iter().map(|s| s.to_string()).collect();
+        header.extend(CONTROL_KEYS.iter().map(|s| s.to_string()));
+        header
+    }
+
+    /// Append a row for the aircraft's current state
+    /// # Arguments
+    /// * `time` - simulation time of the row [s]
+    /// * `id` - index of the aircraft in the world
+    /// * `aircraft` - aircraft to log
+    pub fn record(&mut self, time: f64, id: usize, aircraft: &Aircraft) -> std::io::Result<()> {
+
+        let position = aircraft.position();
+        let velocity = aircraft.velocity();
+        let (roll, pitch, yaw) = quat_to_euler(&aircraft.attitude());
+        let rates = aircraft.rates();
+        let (force, moment) = aircraft.forces();
+
+        let mut row = vec![
+            time,
+            id as f64,
+            position[0], position[1], position[2],
+            velocity[0], velocity[1], velocity[2],
+            roll, pitch, yaw,
+            rates[0], rates[1], rates[2],
+            force[0], force[1], force[2],
+            moment[0], moment[1], moment[2]
+        ];
+        row.extend(CONTROL_KEYS.iter().map(|key| aircraft.controls.get(*key).copied().unwrap_or(0.0)));
+
+        let row: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        writeln!(self.writer, "{}", row.join(","))
+    }
+
+    /// Write any buffered rows to the file, this also happens when the logger is dropped
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+    use aerso::types::{UnitQuaternion, Vector3};
+
+    #[test]
+    fn world_writes_header_and_row_per_step() {
+        let path = std::env::temp_dir().join(format!("flyer_telemetry_{}.csv", std::process::id()));
+        let mut world = World::default();
+        for y in [0.0, 100.0] {
+            world.add_aircraft(Aircraft::new(
+                "TO",
+                Vector3::new(0.0, y, -500.0),
+                Vector3::new(50.0, 0.0, 0.0),
+                UnitQuaternion::identity(),
+                Vector3::zeros(),
+                None,
+                None
+            ));
+        }
+
+        world.enable_telemetry(&path).unwrap();
+        let steps = world.step(5.0 / world.settings.simulation_frequency);
+        world.disable_telemetry().unwrap();
+        // Steps after telemetry is turned off aren't logged
+        world.step(1.0 / world.settings.simulation_frequency);
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(steps, 5);
+        assert_eq!(lines[0], Telemetry::header().join(","));
+        assert_eq!(lines.len(), 1 + steps * 2);
+        for (idx, line) in lines[1..].iter().enumerate() {
+            let row: Vec<f64> = line.split(',').map(|value| value.parse().unwrap()).collect();
+            assert_eq!(row.len(), Telemetry::header().len());
+            assert_eq!(row[1], (idx % 2) as f64);
+        }
+
+        // The last row is the second aircraft after the final logged step, its forces match the aircraft's own
+        let last: Vec<f64> = lines[lines.len() - 1].split(',').map(|value| value.parse().unwrap()).collect();
+        let mut aircraft = Aircraft::new(
+            "TO",
+            Vector3::new(0.0, 100.0, -500.0),
+            Vector3::new(50.0, 0.0, 0.0),
+            UnitQuaternion::identity(),
+            Vector3::zeros(),
+            None,
+            None
+        );
+        for _ in 0..steps {
+            aircraft.step(1.0 / world.settings.simulation_frequency);
+        }
+        let (force, moment) = aircraft.forces();
+        let fx = Telemetry::header().iter().position(|column| column == "fx").unwrap();
+        for axis in 0..3 {
+            assert!((last[fx + axis] - force[axis]).abs() < 1e-6 * force.norm());
+            assert!((last[fx + 3 + axis] - moment[axis]).abs() < 1e-6 * moment.norm().max(1.0));
+        }
+        assert!(force[2] < 0.0, "no lift in {:?}", force);
+    }
+}
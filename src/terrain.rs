@@ -21,15 +21,17 @@ use glam::Vec2;
 use tiny_skia::*;
 
 #[allow(dead_code)] 
+#[derive(Clone)]
 pub struct TerrainConfig {
     name: String,
     field_density: f32,
     land_types: Vec<String>,
     water_cutoff: f32,
     beach_thickness: f32,
-    forest_tree_density: f32,
-    orchard_tree_density: f32,
-    orchard_flower_density: f32
+    pub forest_tree_density: f32,  // probability of a tree on a wooded forest tile, saturates at 1
+    pub crop_density: f32,  // probability of a bushel on a planted crop tile, saturates at 1
+    pub orchard_tree_density: f32,  // probability of a tree on an orchard tile, saturates at 1
+    pub orchard_flower_density: f32  // probability of flowers on an orchard tile without a tree, saturates at 1 - orchard_tree_density
 }
 
 impl TerrainConfig {
@@ -44,12 +46,13 @@ impl TerrainConfig {
             .map(|s| s.chars().next().unwrap_or_default())
             .collect();
 
-        self.name = format!("fd{}lt{}wc{}bt{}ftd{}otd{}ofd{}",
+        self.name = format!("fd{}lt{}wc{}bt{}ftd{}cd{}otd{}ofd{}",
             self.field_density, 
             land_letters,
             self.water_cutoff,
             self.beach_thickness,
             self.forest_tree_density,
+            self.crop_density,
             self.orchard_tree_density,
             self.orchard_flower_density
         );
@@ -67,6 +70,7 @@ impl Default for TerrainConfig {
             water_cutoff: -0.1,
             beach_thickness: 0.04,
             forest_tree_density: 0.6,
+            crop_density: 1.0,
             orchard_tree_density: 0.1,
             orchard_flower_density: 0.1
         }
@@ -87,22 +91,80 @@ pub struct StaticObject {
     pub pos: Vec2 // position in [m] on the map
 }
 
+/// Positions of the generators in [RandomFuncs], enough to reproduce every later draw
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RandomState {
+    pub seed: u32,  // master seed
+    pub word_pos: u128,  // position of the main generator
+    pub streams: HashMap<String, u128>  // position of each named sub-stream
+}
+
 pub struct RandomFuncs {
+    seed: u32,
     simplex_noise: OpenSimplex,
     sampler: Uniform<f64>,
-    rng: ChaCha8Rng
+    rng: ChaCha8Rng,
+    streams: HashMap<String, ChaCha8Rng>
 }
 
 impl RandomFuncs {
     pub fn new(seed: u32) -> Self {
 
         Self {
+            seed,
             simplex_noise: OpenSimplex::new(seed),
             sampler: Uniform::new(0.0, 1.0),
-            rng: ChaCha8Rng::seed_from_u64(seed as u64)
+            rng: ChaCha8Rng::seed_from_u64(seed as u64),
+            streams: HashMap::new()
         }
 
     }
+
+    /// Generator for a named sub-stream, e.g. "wind", created on first use
+    /// # Arguments
+    /// * `name` - name of the stream
+    ///
+    /// Each name picks its own ChaCha stream under the master seed, so a stream's draws depend only on the seed
+    /// and its name. Adding a new stream, or drawing from another one, never changes the sequence of an existing stream.
+    pub fn stream(&mut self, name: &str) -> &mut ChaCha8Rng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| named_stream(seed, name))
+    }
+
+    /// Capture the position of the main generator and every sub-stream created so far
+    #[allow(dead_code)]
+    pub fn save_state(&self) -> RandomState {
+        RandomState {
+            seed: self.seed,
+            word_pos: self.rng.get_word_pos(),
+            streams: self.streams
+                .iter()
+                .map(|(name, rng)| (name.clone(), rng.get_word_pos()))
+                .collect()
+        }
+    }
+
+    /// Return every generator to a saved position, sub-streams created since the save are dropped
+    /// # Arguments
+    /// * `state` - state from [RandomFuncs::save_state]
+    #[allow(dead_code)]
+    pub fn restore_state(&mut self, state: &RandomState) {
+        *self = RandomFuncs::new(state.seed);
+        self.rng.set_word_pos(state.word_pos);
+        for (name, word_pos) in state.streams.iter() {
+            self.stream(name).set_word_pos(*word_pos);
+        }
+    }
+}
+
+fn named_stream(seed: u32, name: &str) -> ChaCha8Rng {
+    // FNV-1a keeps stream ids stable across builds, unlike the std hasher
+    let id = name.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+    rng.set_stream(id);
+    rng
 }
 
 pub struct Terrain {
@@ -335,10 +397,15 @@ impl Terrain {
 
     }
 
-    fn crops(&self, pos: Vec2) -> (Tile, Option<StaticObject>) {
+    fn crops(&mut self, pos: Vec2) -> (Tile, Option<StaticObject>) {
         let object_placement = self.noise(pos[0] as f64, pos[1] as f64, 6.0, Some(HashMap::from([(5, 1), (10, 1)])), Some(true)) as f32;
-        
-        let so = if object_placement < -0.25 {
+        // Drawn from its own stream so the crop density doesn't shift the main generator used by the other biomes
+        let sampler = self.random_funcs.sampler;
+        let bushel_probability = sampler.sample(self.random_funcs.stream("crops")) as f32;
+
+        let so = if bushel_probability >= self.config.crop_density {
+            None
+        } else if object_placement < -0.25 {
             // Add green bushel
             Some(StaticObject {
                 name: "GreenBushel".to_string(),
@@ -458,3 +525,110 @@ impl Terrain {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Objects generated on a map covered by a single biome
+    fn objects(land_type: &str, config: TerrainConfig) -> Vec<StaticObject> {
+        let mut terrain = Terrain {
+            seed: 7,
+            area: vec![100, 100],
+            scaling: 1.0,
+            config: TerrainConfig { land_types: vec![land_type.to_string()], ..config },
+            water_present: false,
+            random_funcs: RandomFuncs::new(7)
+        };
+        terrain.generate_map().1
+    }
+
+    #[test]
+    fn doubling_forest_density_doubles_trees() {
+        let sparse = objects("forest", TerrainConfig { forest_tree_density: 0.3, ..TerrainConfig::default() }).len();
+        let dense = objects("forest", TerrainConfig { forest_tree_density: 0.6, ..TerrainConfig::default() }).len();
+        let ratio = dense as f32 / sparse as f32;
+        assert!(sparse > 100 && (ratio - 2.0).abs() < 0.2, "{} trees at 0.3, {} at 0.6", sparse, dense);
+    }
+
+    #[test]
+    fn density_is_deterministic_per_seed() {
+        let config = TerrainConfig { forest_tree_density: 0.45, ..TerrainConfig::default() };
+        let first: Vec<Vec2> = objects("forest", config.clone()).iter().map(|o| o.pos).collect();
+        let second: Vec<Vec2> = objects("forest", config).iter().map(|o| o.pos).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn crop_density_thins_bushels() {
+        let full = objects("crops", TerrainConfig::default()).len();
+        let half = objects("crops", TerrainConfig { crop_density: 0.5, ..TerrainConfig::default() }).len();
+        let none = objects("crops", TerrainConfig { crop_density: 0.0, ..TerrainConfig::default() }).len();
+        let ratio = half as f32 / full as f32;
+        assert!(full > 100 && (ratio - 0.5).abs() < 0.1, "{} bushels at full density, {} at half", full, half);
+        assert_eq!(none, 0);
+    }
+
+    // Next `count` uniform draws from a named stream
+    fn draws(random_funcs: &mut RandomFuncs, name: &str, count: usize) -> Vec<f64> {
+        (0..count).map(|_| random_funcs.stream(name).gen::<f64>()).collect()
+    }
+
+    #[test]
+    fn same_stream_name_is_reproducible() {
+        let mut first = RandomFuncs::new(11);
+        let mut second = RandomFuncs::new(11);
+
+        // Drawing from other streams first, or adding new ones, leaves the sequence unchanged
+        draws(&mut second, "terrain", 50);
+        draws(&mut second, "aircraft_0", 5);
+        assert_eq!(draws(&mut first, "wind", 100), draws(&mut second, "wind", 100));
+        assert_ne!(draws(&mut RandomFuncs::new(12), "wind", 100), draws(&mut RandomFuncs::new(11), "wind", 100));
+    }
+
+    #[test]
+    fn different_stream_names_are_independent() {
+        let mut random_funcs = RandomFuncs::new(11);
+        let wind = draws(&mut random_funcs, "wind", 10000);
+        let terrain = draws(&mut random_funcs, "terrain", 10000);
+        assert_ne!(wind, terrain);
+
+        // Uncorrelated, also when one sequence is shifted against the other
+        let correlation = |a: &[f64], b: &[f64]| {
+            let n = a.len() as f64;
+            let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+            let covariance: f64 = a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum::<f64>() / n;
+            covariance / (1.0 / 12.0)  // variance of a uniform draw on [0, 1)
+        };
+        for shift in 0..4 {
+            let r = correlation(&wind[shift..], &terrain[..10000 - shift]);
+            assert!(r.abs() < 0.05, "correlation {} at shift {}", r, shift);
+        }
+    }
+
+    #[test]
+    fn restored_state_repeats_draws() {
+        let mut random_funcs = RandomFuncs::new(5);
+        draws(&mut random_funcs, "wind", 7);
+        draws(&mut random_funcs, "terrain", 3);
+        random_funcs.rng.gen::<f64>();
+
+        // Saved state survives serialisation, as it would in a snapshot
+        let saved: RandomState = serde_json::from_str(&serde_json::to_string(&random_funcs.save_state()).unwrap()).unwrap();
+        let expected = (
+            draws(&mut random_funcs, "wind", 20),
+            draws(&mut random_funcs, "terrain", 20),
+            draws(&mut random_funcs, "crops", 20),
+            random_funcs.rng.gen::<f64>()
+        );
+
+        random_funcs.restore_state(&saved);
+        let restored = (
+            draws(&mut random_funcs, "wind", 20),
+            draws(&mut random_funcs, "terrain", 20),
+            draws(&mut random_funcs, "crops", 20),
+            random_funcs.rng.gen::<f64>()
+        );
+        assert_eq!(restored, expected);
+    }
+}
@@ -1,55 +1,182 @@
-use crate::aircraft::Aircraft;
+use crate::aircraft::{Aerodynamics, Aircraft, PowerPlant};
+use crate::utils::euler_to_quat;
 
 use aerso::types::*;
-use std::{env, path::PathBuf};
+use std::{collections::HashMap, env, fmt, f64::consts::PI, path::PathBuf};
 
 extern crate nalgebra as na;
-use argmin::core::{CostFunction, Error};
+use argmin::core::{CostFunction, Error, Executor};
+use argmin::solver::neldermead::NelderMead;
 use na::DVector;
 
+const GRAVITY: f64 = 9.81;  // [m/s^2]
+const AIR_DENSITY: f64 = 1.225;  // density of the aircraft's constant density model [Kg/m^3]
+const ALPHA_RANGE: (f64, f64) = (-4.0 * (PI / 180.0), 30.0 * (PI / 180.0));  // angle of attack range the aero model is valid over [rads]
+
+/// Reason a trim condition can't be held
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrimError {
+    /// The wing can't carry the weight component normal to the flight path before it stalls
+    Stall {
+        /// Lift coefficient needed to hold the flight path
+        required_lift_coefficient: f64,
+        /// Highest lift coefficient the wing reaches
+        max_lift_coefficient: f64
+    },
+    /// Full throttle can't overcome drag and the weight component along the flight path
+    Thrust {
+        /// Thrust needed to hold the flight path [N]
+        required: f64,
+        /// Thrust at full throttle [N]
+        available: f64,
+        /// Steepest flight path angle full throttle can hold at this airspeed [rads]
+        max_flight_path_angle: f64
+    }
+}
+
+impl fmt::Display for TrimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrimError::Stall { required_lift_coefficient, max_lift_coefficient } => write!(
+                f,
+                "trim needs a lift coefficient of {:.3} but the wing stalls at {:.3}",
+                required_lift_coefficient, max_lift_coefficient
+            ),
+            TrimError::Thrust { required, available, max_flight_path_angle } => write!(
+                f,
+                "trim needs {:.0} N of thrust but full throttle gives {:.0} N, the steepest flight path angle is {:.2} deg",
+                required, available, max_flight_path_angle.to_degrees()
+            )
+        }
+    }
+}
+
+impl std::error::Error for TrimError {}
+
 #[derive(Clone, Copy)]
 pub struct Trim {
     /// Altitude to maintain
     pub alt: f64,
     /// Airspeed to maintain
-    pub airspeed: f64
+    pub airspeed: f64,
+    /// Flight path angle to maintain [rads], positive is climbing, ignored when the throttle is fixed
+    pub flight_path_angle: f64,
+    /// Throttle setting to hold fixed, if None the throttle is trimmed too. Fixing the throttle frees the flight
+    /// path angle so the trim finds the climb or descent that setting holds at the airspeed
+    pub tla: Option<f64>,
+    /// Bank angle of a steady coordinated turn [rads], positive banks right to turn right, 0 is straight flight
+    pub bank_angle: f64,
+    /// Length of the simulated flight each set of parameters is scored over [s]
+    pub exp_len: f64
 }
 
 impl Trim {
 
     const FPS: u32 = 100;
-    const EXP_LEN: f32 = 200.0;
+    const EXP_LEN: f64 = 200.0;
+
+    /// Trim for straight and level flight with the throttle trimmed too
+    /// # Arguments
+    /// * `alt` - altitude to maintain [m], z is down so this is negative above the ground
+    /// * `airspeed` - airspeed to maintain [m/s]
+    pub fn new(alt: f64, airspeed: f64) -> Self {
+        Self {
+            alt,
+            airspeed,
+            flight_path_angle: 0.0,
+            tla: None,
+            bank_angle: 0.0,
+            exp_len: Self::EXP_LEN
+        }
+    }
+
+    /// Rate of turn [rads/s] of a coordinated turn at the bank angle and airspeed, g tan(bank) / airspeed
+    pub fn turn_rate(&self) -> f64 {
+        GRAVITY * self.bank_angle.tan() / self.airspeed
+    }
+
+    /// Check the flight path can be held at all before searching for a trim
+    ///
+    /// Balances forces along and normal to the flight path with the elevator centred and no pitch rate, ignoring
+    /// the thrust component normal to the path. This is a quick estimate to catch conditions the solver can never
+    /// meet, such as a climb steeper than full throttle allows, not a replacement for the trim itself. With the
+    /// throttle fixed the flight path angle is free, so only the stall is checked and in level flight. In a turn the
+    /// wing carries the load factor 1 / cos(bank).
+    pub fn feasibility(&self) -> Result<(), TrimError> {
+
+        let flight_path_angle = if self.tla.is_some() { 0.0 } else { self.flight_path_angle };
+
+        let aero = Aerodynamics::from_json("TO", Self::data_path().as_deref());
+        let weight = aero.mass * GRAVITY;
+        let lift_per_coefficient = 0.5 * AIR_DENSITY * self.airspeed.powf(2.0) * aero.wing_area;
+
+        // Lowest angle of attack giving enough lift, the lift curve isn't monotonic past the stall
+        let load_factor = 1.0 / self.bank_angle.cos();
+        let required_lift_coefficient = load_factor * weight * flight_path_angle.cos() / lift_per_coefficient;
+        let alphas = (0..=1000).map(|i| ALPHA_RANGE.0 + (ALPHA_RANGE.1 - ALPHA_RANGE.0) * i as f64 / 1000.0);
+        let alpha = match alphas.clone().find(|&alpha| lift_coefficient(&aero, alpha) >= required_lift_coefficient) {
+            Some(alpha) => alpha,
+            None => return Err(TrimError::Stall {
+                required_lift_coefficient,
+                max_lift_coefficient: alphas.map(|alpha| lift_coefficient(&aero, alpha)).fold(f64::MIN, f64::max)
+            })
+        };
+
+        if self.tla.is_some() {
+            return Ok(());
+        }
+
+        let drag = lift_per_coefficient * drag_coefficient(&aero, alpha);
+        let required = drag + weight * flight_path_angle.sin();
+        let available = PowerPlant::pt6().thrust(1.0);
+
+        if required > available {
+            return Err(TrimError::Thrust {
+                required,
+                available,
+                max_flight_path_angle: ((available - drag) / weight).clamp(-1.0, 1.0).asin()
+            });
+        }
 
+        Ok(())
+    }
+
+    /// Evaluate the cost of trimming when given the input params [pitch, elevator, tla, aileron, rudder, sideslip],
+    /// with the flight path angle in place of tla when the throttle is fixed. The lateral params may be left off for
+    /// straight flight and are then zero.
+    ///
+    /// The cost integrates the squared residuals of airspeed along the flight path, vertical speed, body rates
+    /// against those of a steady turn at [Trim::turn_rate] scaled by airspeed, and sideslip. The side force from the
+    /// yaw rate means a turn at g tan(bank) / airspeed needs a little sideslip to balance, so sideslip is a param
+    /// and the residual keeps it to the least the turn holds with.
     pub fn eval(self, u: &Vec<f64>) -> f64 {
 
         let dt = 1.0/Self::FPS as f64;
 
-        // This allows the trim to run in test suite without placing data files at root dir
-        let f_path = if env::current_dir().unwrap().file_name().unwrap() == PathBuf::from("flyer-env") {
-            Some(String::from("flyer_env/envs/data/"))
-        } else {
-            None
+        let flight_path_angle = match self.tla {
+            Some(_) => u[2],
+            None => self.flight_path_angle
         };
 
-        let mut aircraft = Aircraft::new(
-            "TO",
-            Vector3::new(0.0, 0.0, self.alt),
-            Vector3::new(self.airspeed, 0.0, 0.0),
-            UnitQuaternion::from_euler_angles(0.0, u[0], 0.0),
-            Vector3::zeros(),
-            None,
-            f_path
-        );
-
-        let controls = vec![0.0, u[1], u[2], 0.0];
+        // Velocity along the flight path, z is down so a climb has negative vertical velocity
+        let u_target = self.airspeed * flight_path_angle.cos();
+        let w_target = -self.airspeed * flight_path_angle.sin();
+
+        let mut aircraft = self.initial_aircraft(u);
+        let turn_rates = aircraft.rates();
         let mut total_cost = 0.0;
         let mut time = 0.0;
-        
-        for _ in 0..(Self::FPS * (Self::EXP_LEN as u32)) {
-            aircraft.aff_body.step(dt, &controls);
+
+        for _ in 0..(self.exp_len * Self::FPS as f64).round() as usize {
+            aircraft.step(dt);
+            let velocity = aircraft.velocity();
 
             if time > 0.1 {
-                let current_cost = (aircraft.velocity()[0] - self.airspeed).powf(2.0) + (aircraft.velocity()[2]).powf(2.0);
+                let sideslip = ((aircraft.attitude().inverse() * velocity)[1] / velocity.norm()).asin();
+                let current_cost = (velocity[0].hypot(velocity[1]) - u_target).powf(2.0) +
+                    (velocity[2] - w_target).powf(2.0) +
+                    (self.airspeed * (aircraft.rates() - turn_rates).norm()).powf(2.0) +
+                    sideslip.powf(2.0);
                 total_cost += current_cost * dt;
             };
             time += dt;
@@ -58,6 +185,96 @@ impl Trim {
         total_cost
 
     }
+
+    /// Aircraft at the start of the trimmed flight, pointing north with the controls set from the params
+    /// # Arguments
+    /// * `u` - trim params as taken by [Trim::eval]
+    ///
+    /// The velocity is at the sideslip and flight path angle, which with the bank and pitch set the angle of attack.
+    /// In a turn the body rates are the turn rate about the vertical resolved into the body frame.
+    pub fn initial_aircraft(&self, u: &[f64]) -> Aircraft {
+
+        let (tla, flight_path_angle) = match self.tla {
+            Some(tla) => (tla, u[2]),
+            None => (u[2], self.flight_path_angle)
+        };
+        let (aileron, rudder) = if u.len() >= 5 { (u[3], u[4]) } else { (0.0, 0.0) };
+
+        let (sin_roll, cos_roll) = self.bank_angle.sin_cos();
+        let (sin_pitch, cos_pitch) = u[0].sin_cos();
+        let attitude = euler_to_quat(self.bank_angle, u[0], 0.0);
+
+        let beta = if u.len() > 5 { u[5] } else { 0.0 };
+        let (sin_beta, cos_beta) = beta.sin_cos();
+        // Vertical velocity of the body velocity (cos(alpha) cos(beta), sin(beta), sin(alpha) cos(beta)) per unit
+        // airspeed is a cos(alpha) + b sin(alpha) + sin(roll) cos(pitch) sin(beta), solved for the alpha holding the
+        // flight path angle
+        let (a, b) = (-sin_pitch * cos_beta, cos_pitch * cos_roll * cos_beta);
+        let c = -flight_path_angle.sin() - sin_roll * cos_pitch * sin_beta;
+        let alpha = b.atan2(a) - (c / a.hypot(b)).clamp(-1.0, 1.0).acos();
+        let velocity = attitude * Vector3::new(alpha.cos() * cos_beta, sin_beta, alpha.sin() * cos_beta) * self.airspeed;
+
+        let turn_rate = self.turn_rate();
+        let rates = Vector3::new(-turn_rate * sin_pitch, turn_rate * sin_roll * cos_pitch, turn_rate * cos_roll * cos_pitch);
+
+        let controls = HashMap::from([
+            ("aileron".to_string(), aileron),
+            ("elevator".to_string(), u[1]),
+            ("tla".to_string(), tla),
+            ("rudder".to_string(), rudder)
+        ]);
+
+        Aircraft::new(
+            "TO",
+            Vector3::new(0.0, 0.0, self.alt),
+            velocity,
+            attitude,
+            rates,
+            Some(controls),
+            Self::data_path()
+        )
+    }
+
+    /// Search for the trim parameters with Nelder-Mead, returning them with their cost
+    /// # Arguments
+    /// * `simplex` - initial simplex of parameter vectors, one more than the number of parameters
+    /// * `max_iters` - iteration limit of the search
+    ///
+    /// The condition is checked with [Trim::feasibility] once up front, so a condition that can't be held fails
+    /// with a [TrimError] before any flight is simulated.
+    pub fn solve(self, simplex: Vec<DVector<f64>>, max_iters: u64) -> Result<(DVector<f64>, f64), Error> {
+        self.feasibility()?;
+        let solver = NelderMead::new(simplex).with_sd_tolerance(1e-8)?;
+        let res = Executor::new(self, solver)
+            .configure(|state| state.max_iters(max_iters))
+            .run()?;
+        let cost = res.state.best_cost;
+        let param = res.state.best_param.ok_or_else(|| Error::msg("trim search finished without a best parameter"))?;
+        Ok((param, cost))
+    }
+
+    // This allows the trim to run in test suite without placing data files at root dir
+    fn data_path() -> Option<String> {
+        if env::current_dir().unwrap().file_name().unwrap() == PathBuf::from("flyer-env") {
+            Some(String::from("flyer_env/envs/data/"))
+        } else {
+            None
+        }
+    }
+}
+
+// Lift coefficient with the elevator centred and no pitch rate
+fn lift_coefficient(aero: &Aerodynamics, alpha: f64) -> f64 {
+    let lift = &aero.lift_data;
+    lift.c_L_0 + lift.c_L_alpha * alpha + lift.c_L_alpha2 * alpha.powf(2.0) + lift.c_L_alpha3 * alpha.powf(3.0) +
+        lift.c_L_alpha4 * alpha.powf(4.0)
+}
+
+// Drag coefficient with the elevator centred and no pitch rate
+fn drag_coefficient(aero: &Aerodynamics, alpha: f64) -> f64 {
+    let drag = &aero.drag_data;
+    drag.c_D_0 + drag.c_D_alpha * alpha + drag.c_D_alpha2 * alpha.powf(2.0) + drag.c_D_alpha3 * alpha.powf(3.0) +
+        drag.c_D_alpha4 * alpha.powf(4.0)
 }
 
 impl CostFunction for Trim {
@@ -68,3 +285,123 @@ impl CostFunction for Trim {
         Ok(self.eval(param.data.as_vec()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::dvector;
+
+    const TEST_EXP_LEN: f64 = 10.0;  // shorter experiment so the searches run quickly in debug builds [s]
+    const TEST_MAX_ITERS: u64 = 120;
+    const TEST_TURN_MAX_ITERS: u64 = 600;  // the turn searches six parameters rather than three
+
+    // Fly the trimmed parameters for the experiment length, returning the aircraft at the end
+    fn fly(trim: &Trim, u: &DVector<f64>) -> Aircraft {
+        let mut aircraft = trim.initial_aircraft(u.as_slice());
+        for _ in 0..(trim.exp_len * Trim::FPS as f64).round() as usize {
+            aircraft.step(1.0 / Trim::FPS as f64);
+        }
+        aircraft
+    }
+
+    #[test]
+    fn five_degree_climb_is_feasible() {
+        let trim = Trim { flight_path_angle: 5.0_f64.to_radians(), ..Trim::new(-1000.0, 100.0) };
+        assert_eq!(trim.feasibility(), Ok(()));
+    }
+
+    #[test]
+    fn steep_climb_is_thrust_limited() {
+        let trim = Trim { flight_path_angle: 30.0_f64.to_radians(), ..Trim::new(-1000.0, 100.0) };
+        match trim.feasibility() {
+            Err(TrimError::Thrust { required, available, max_flight_path_angle }) => {
+                assert!(required > available);
+                assert!(max_flight_path_angle > 5.0_f64.to_radians() && max_flight_path_angle < trim.flight_path_angle);
+            },
+            other => panic!("expected a thrust limit, got {:?}", other)
+        }
+
+        // The search fails straight away with the same diagnosis
+        let simplex = vec![dvector![0.0, 0.0, 1.0], dvector![0.1, 0.0, 1.0], dvector![0.0, 0.1, 1.0], dvector![0.0, 0.0, 0.9]];
+        let err = trim.solve(simplex, TEST_MAX_ITERS).unwrap_err();
+        assert!(matches!(err.downcast_ref::<TrimError>(), Some(TrimError::Thrust { .. })));
+    }
+
+    #[test]
+    fn slow_flight_stalls() {
+        let trim = Trim::new(-1000.0, 20.0);
+        assert!(matches!(trim.feasibility(), Err(TrimError::Stall { .. })));
+    }
+
+    #[test]
+    fn five_degree_climb_trims() {
+        let gamma = 5.0_f64.to_radians();
+        let trim = Trim { flight_path_angle: gamma, exp_len: TEST_EXP_LEN, ..Trim::new(-1000.0, 100.0) };
+
+        let simplex = vec![
+            dvector![gamma, 0.0, 0.8],
+            dvector![gamma + 0.05, 0.0, 0.8],
+            dvector![gamma, 0.05, 0.8],
+            dvector![gamma, 0.0, 0.9]
+        ];
+        let (u, cost) = trim.solve(simplex, TEST_MAX_ITERS).unwrap();
+
+        // Residuals are integrated over the whole experiment so a small cost means they stay small throughout
+        assert!(cost < 1.0, "trim cost {}", cost);
+
+        let climb_rate = -fly(&trim, &u).velocity()[2];
+        assert!((climb_rate - trim.airspeed * gamma.sin()).abs() < 0.5, "climb rate {}", climb_rate);
+    }
+
+    #[test]
+    fn fixed_throttle_trims_descent() {
+        let trim = Trim { tla: Some(0.5), exp_len: TEST_EXP_LEN, ..Trim::new(-1000.0, 100.0) };
+
+        let simplex = vec![
+            dvector![0.0, 0.0, 0.0],
+            dvector![0.05, 0.0, 0.0],
+            dvector![0.0, 0.05, 0.0],
+            dvector![0.0, 0.0, -0.05]
+        ];
+        let (u, cost) = trim.solve(simplex, TEST_MAX_ITERS).unwrap();
+        assert!(cost < 1.0, "trim cost {}", cost);
+
+        // Half throttle is short of the level flight drag so the trim settles into a descent
+        let gamma = u[2];
+        assert!(gamma < -2.0_f64.to_radians(), "flight path angle {}", gamma.to_degrees());
+
+        let aircraft = fly(&trim, &u);
+        assert!((aircraft.velocity().norm() - trim.airspeed).abs() < 0.5, "airspeed {}", aircraft.velocity().norm());
+        assert!((-aircraft.velocity()[2] - trim.airspeed * gamma.sin()).abs() < 0.5);
+    }
+
+    #[test]
+    fn coordinated_turn_trims() {
+        let bank = 30.0_f64.to_radians();
+        let trim = Trim { bank_angle: bank, exp_len: TEST_EXP_LEN, ..Trim::new(-1000.0, 100.0) };
+
+        let start = dvector![0.0, -0.05, 0.8, 0.0, 0.0, 0.0];
+        let mut simplex = vec![start.clone()];
+        for idx in 0..start.len() {
+            let mut vertex = start.clone();
+            vertex[idx] += 0.02;
+            simplex.push(vertex);
+        }
+        let (u, cost) = trim.solve(simplex, TEST_TURN_MAX_ITERS).unwrap();
+        assert!(cost < 0.01, "trim cost {}", cost);
+        assert!(u[5].abs() < 1.0_f64.to_radians(), "sideslip {}", u[5].to_degrees());
+
+        // Aerodynamic moments balance the gyroscopic moment of the turn so the body rates hold
+        let aircraft = trim.initial_aircraft(u.as_slice());
+        let inertia = Aerodynamics::from_json("TO", None).inertia;
+        let rates = aircraft.rates();
+        let net_moment = aircraft.forces().1 - rates.cross(&(inertia * rates));
+        assert!(net_moment.norm() < 10.0, "net moment {:?}", net_moment);
+
+        // The track turns at g tan(bank) / airspeed throughout
+        let track = |velocity: Vector3<f64>| velocity[1].atan2(velocity[0]);
+        let turned = track(fly(&trim, &u).velocity()) - track(aircraft.velocity());
+        let expected = GRAVITY * bank.tan() / trim.airspeed * trim.exp_len;
+        assert!((turned - expected).abs() < 0.01 * expected, "turned {} rads, expected {} rads", turned, expected);
+    }
+}
@@ -0,0 +1,93 @@
+//! Attitude conversions shared across the crate.
+//!
+//! All conversions use the aerospace ZYX (yaw, pitch, roll) sequence: starting from the north-east-down world frame,
+//! rotate by yaw about z, then pitch about the new y, then roll about the new x to reach the body frame. Angles
+//! are in radians, the quaternion and DCM rotate body-frame vectors into the world frame.
+
+use aerso::types::{Matrix3, UnitQuaternion};
+
+const GIMBAL_LOCK_TOLERANCE: f64 = 1e-12;  // distance of sin(pitch) from ±1 treated as gimbal lock
+
+/// Euler angles (roll, pitch, yaw) [rads] of an attitude
+/// # Arguments
+/// * `q` - attitude quaternion
+///
+/// Roll and yaw are in (-π, π] and pitch in [-π/2, π/2]. At +90° pitch only yaw - roll is defined and at -90° only
+/// yaw + roll, so roll is reported as zero and yaw carries that combination.
+pub fn quat_to_euler(q: &UnitQuaternion<f64>) -> (f64, f64, f64) {
+    let (w, x, y, z) = (q.w, q.i, q.j, q.k);
+
+    let sin_pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0);
+    let pitch = sin_pitch.asin();
+
+    if sin_pitch.abs() >= 1.0 - GIMBAL_LOCK_TOLERANCE {
+        let yaw = (-2.0 * (x * y - w * z)).atan2(1.0 - 2.0 * (x * x + z * z));
+        return (0.0, pitch, yaw);
+    }
+
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+    (roll, pitch, yaw)
+}
+
+/// Attitude quaternion from Euler angles [rads]
+/// # Arguments
+/// * `roll` - rotation about the body x-axis
+/// * `pitch` - rotation about the intermediate y-axis
+/// * `yaw` - rotation about the world z-axis
+#[allow(dead_code)]
+pub fn euler_to_quat(roll: f64, pitch: f64, yaw: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_euler_angles(roll, pitch, yaw)
+}
+
+/// Direction cosine matrix of an attitude, v_world = dcm * v_body
+/// # Arguments
+/// * `q` - attitude quaternion
+pub fn quat_to_dcm(q: &UnitQuaternion<f64>) -> Matrix3<f64> {
+    q.to_rotation_matrix().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerso::types::Vector3;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn euler_round_trip() {
+        for &roll in [-3.0, -1.2, 0.0, 0.4, 2.9].iter() {
+            for &pitch in [-1.5, -0.7, 0.0, 0.3, 1.5].iter() {
+                for &yaw in [-3.1, -0.5, 0.0, 1.1, 3.0].iter() {
+                    let (r, p, y) = quat_to_euler(&euler_to_quat(roll, pitch, yaw));
+                    assert!((r - roll).abs() < 1e-9 && (p - pitch).abs() < 1e-9 && (y - yaw).abs() < 1e-9,
+                        "({}, {}, {}) returned as ({}, {}, {})", roll, pitch, yaw, r, p, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gimbal_lock_keeps_the_defined_combination() {
+        let (roll, yaw) = (0.3, 0.5);
+
+        let nose_up = euler_to_quat(roll, FRAC_PI_2, yaw);
+        let (r, p, y) = quat_to_euler(&nose_up);
+        assert_eq!(r, 0.0);
+        assert!((p - FRAC_PI_2).abs() < 1e-6 && (y - (yaw - roll)).abs() < 1e-6);
+        assert!(euler_to_quat(r, p, y).angle_to(&nose_up) < 1e-6);
+
+        let nose_down = euler_to_quat(roll, -FRAC_PI_2, yaw);
+        let (r, p, y) = quat_to_euler(&nose_down);
+        assert_eq!(r, 0.0);
+        assert!((p + FRAC_PI_2).abs() < 1e-6 && (y - (yaw + roll)).abs() < 1e-6);
+        assert!(euler_to_quat(r, p, y).angle_to(&nose_down) < 1e-6);
+    }
+
+    #[test]
+    fn dcm_rotates_body_to_world() {
+        // Heading east pitched up 30°, the nose points east and up, which is -z in north-east-down
+        let dcm = quat_to_dcm(&euler_to_quat(0.0, 30.0_f64.to_radians(), FRAC_PI_2));
+        let nose = dcm * Vector3::x();
+        assert!((nose - Vector3::new(0.0, 3.0_f64.sqrt() / 2.0, -0.5)).norm() < 1e-9);
+    }
+}
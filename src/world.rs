@@ -1,16 +1,20 @@
 use crate::terrain::{Tile, StaticObject, TerrainConfig, Terrain, RandomFuncs};
 use crate::aircraft::Aircraft;
-use crate::runway::Runway;
-
-use std::{fs, path::PathBuf};
-use std::collections::HashMap;
+use crate::runway::{Runway, RunwayRegion};
+use crate::collision::{self, CollisionEvent};
+use crate::geodetic::Geodetic;
+use crate::telemetry::Telemetry;
+use crate::utils::{quat_to_euler, quat_to_dcm};
+
+use std::{fs, path::{Path, PathBuf}};
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
 use std::fs::File;
 use std::time::Instant;
 
 use aerso::types::StateView;
 use serde::{Serialize, Deserialize};
-use glam::{Vec2, Vec3};
+use glam::{Mat3, Vec2, Vec3};
 use tiny_skia::*;
 
 use rayon::prelude::*;
@@ -21,24 +25,53 @@ struct TerrainData {
     objects: Vec<StaticObject>
 }
 
+/// Everything the cached terrain layer depends on other than the terrain itself, which can only be changed through
+/// setters that drop the cache
+#[derive(PartialEq)]
+struct TerrainLayerKey {
+    camera: [f64; 4],
+    screen_dims: Vec2,
+    scale: f32,
+    time_of_day: f32,
+    shadow_opacity: f32,
+    fog_density: f32,
+    fog_colour: [u8; 3]
+}
+
 pub struct World {
     pub vehicles: Vec<Aircraft>,
     pub camera: Camera,
     pub controls: Vec<f64>,
-    pub tiles: Vec<Tile>,
-    pub tile_map: HashMap<String, Pixmap>,
-    pub objects: Vec<StaticObject>,
-    pub object_map: HashMap<String, Pixmap>,
+    tiles: Vec<Tile>,
+    tile_map: HashMap<String, Pixmap>,
+    objects: Vec<StaticObject>,
+    object_map: HashMap<String, Pixmap>,
+    shadow_map: HashMap<String, Pixmap>,
+    pub terrain_config: TerrainConfig,
     pub screen_dims: Vec2,
     pub scale: f32,
     origin: Vec2,
     pub settings: Settings,
+    pub sim_time: f64,  // simulated time advanced by step [s]
+    accumulator: f64,  // time handed to step but not yet simulated [s]
+    telemetry: Option<Telemetry>,  // CSV log written at every fixed step, off unless enabled
     pub assets_dir: PathBuf,
     pub terrain_data_dir: PathBuf,
-    pub runway: Option<Runway>,
+    runway: Option<Runway>,
     pub goal: Option<Vec3>,
+    pub geodetic_origin: Option<Geodetic>,  // real-world position of the local origin, if the scenario has one
     pub render_type: String,
-    pos_log: Vec<Vec3>,
+    pub time_of_day: f32,  // local solar time used to light the scene [hours]
+    pub shadow_opacity: f32,  // opacity of the shadows cast by static objects, zero leaves the render unshadowed
+    pub minimap_dims: Vec2,  // size of the minimap overlaid on the world render, zero to disable [pixels]
+    pub minimap_pos: Vec2,  // position of the minimap's top-left corner on screen [pixels]
+    pub fog_density: f32,  // extinction coefficient of the haze, zero disables fog [1/m]
+    pub fog_colour: [u8; 3],  // colour distant terrain fades towards
+    pub anti_aliasing: u32,  // supersampling factor for the world render, 1 disables it and 2 or 4 enable it, others round down
+    terrain_layer: Option<(TerrainLayerKey, Pixmap)>,
+    terrain_renders: usize,  // number of times the terrain layer has been rasterised
+    pub trail_length: usize,  // number of past positions kept in each aircraft's trail
+    trails: Vec<VecDeque<Vec3>>,  // recent positions of each aircraft, indexed like vehicles, oldest first
     area: Vec<usize>
 }
 
@@ -54,16 +87,32 @@ impl Default for World{
             tile_map: HashMap::new(),
             objects: vec![],
             object_map: HashMap::new(),
+            shadow_map: HashMap::new(),
+            terrain_config: TerrainConfig::default(),
             screen_dims: Vec2::new(1024.0, 1024.0),
             scale: 25.0,
             origin: Vec2::new(0.0, 0.0),
             settings: Settings::default(),
+            sim_time: 0.0,
+            accumulator: 0.0,
+            telemetry: None,
             assets_dir: [r"assets"].iter().collect(),
             terrain_data_dir: [r"terrain_data"].iter().collect(),
             runway: None,
             goal: None,
+            geodetic_origin: None,
             render_type: String::from("world"),
-            pos_log: Vec::new(),
+            time_of_day: 12.0,
+            shadow_opacity: 0.0,
+            minimap_dims: Vec2::new(0.0, 0.0),
+            minimap_pos: Vec2::new(0.0, 0.0),
+            fog_density: 0.0,
+            fog_colour: [200, 210, 225],
+            anti_aliasing: 1,
+            terrain_layer: None,
+            terrain_renders: 0,
+            trail_length: 400,
+            trails: vec![],
             area: vec![256, 256]
         }
     }
@@ -93,8 +142,7 @@ impl World {
         scaling: Option<f32>,
         water_present: Option<bool>
     ) {
-        // Build from default, if there are other values we deal with those
-        let terrain_config = TerrainConfig::default();
+        let terrain_config = self.terrain_config.clone();
 
         let area = if let Some(area) = area {
                 area
@@ -224,11 +272,19 @@ impl World {
         self.tiles = tiles;
         self.objects = objects;
 
+        self.shadow_map = object_map.iter().map(|(name, pixmap)| (name.clone(), silhouette(pixmap))).collect();
         self.tile_map = tile_map;
         self.object_map = object_map;
 
         self.area = terrain.area;
+        self.terrain_layer = None;
+
+    }
 
+    /// Set the terrain config used by create_map, the densities form part of the terrain name so maps are regenerated when they change
+    #[allow(dead_code)]
+    pub fn set_terrain_config(&mut self, terrain_config: TerrainConfig) {
+        self.terrain_config = terrain_config;
     }
 
     #[allow(dead_code)]
@@ -267,31 +323,298 @@ impl World {
         self.goal = Some(points);
     }
 
+    /// Clear every aircraft's trail, e.g. at the start of a new episode
+    #[allow(dead_code)]
+    pub fn reset_trail(&mut self) {
+        self.trails.clear();
+    }
+
+    // Add each aircraft's current position to its trail, dropping points once older than the trail
+    fn record_trails(&mut self) {
+        self.trails.resize_with(self.vehicles.len(), VecDeque::new);
+        for (trail, vehicle) in self.trails.iter_mut().zip(self.vehicles.iter()) {
+            let position = vehicle.position();
+            trail.push_back(Vec3::new(position[0] as f32, position[1] as f32, position[2] as f32));
+            while trail.len() > self.trail_length {
+                trail.pop_front();
+            }
+        }
+    }
+
     pub fn create_runway(&mut self) {
-        let runway = Runway::default();
-        self.runway = Some(runway);
+        self.set_runway(Some(Runway::default()));
+    }
+
+    /// Place a runway on the map, or remove it with None
+    pub fn set_runway(&mut self, runway: Option<Runway>) {
+        self.runway = runway;
+        self.terrain_layer = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn runway(&self) -> Option<&Runway> {
+        self.runway.as_ref()
+    }
+
+    /// Replace the map's tiles, e.g. with a map generated outside of create_map
+    #[allow(dead_code)]
+    pub fn set_tiles(&mut self, tiles: Vec<Tile>) {
+        self.tiles = tiles;
+        self.terrain_layer = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// Replace the static objects standing on the map
+    #[allow(dead_code)]
+    pub fn set_objects(&mut self, objects: Vec<StaticObject>) {
+        self.objects = objects;
+        self.terrain_layer = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn objects(&self) -> &[StaticObject] {
+        &self.objects
+    }
+
+    /// Add or replace the sprite drawn for tiles with this asset name
+    #[allow(dead_code)]
+    pub fn set_tile_asset(&mut self, name: &str, pixmap: Pixmap) {
+        self.tile_map.insert(name.to_string(), pixmap);
+        self.terrain_layer = None;
+    }
+
+    /// Add or replace the sprite drawn for objects with this asset name, its shadow is rebuilt to match
+    #[allow(dead_code)]
+    pub fn set_object_asset(&mut self, name: &str, pixmap: Pixmap) {
+        self.shadow_map.insert(name.to_string(), silhouette(&pixmap));
+        self.object_map.insert(name.to_string(), pixmap);
+        self.terrain_layer = None;
+    }
+
+    /// Sprites drawn for tiles, by asset name
+    #[allow(dead_code)]
+    pub fn tile_map(&self) -> &HashMap<String, Pixmap> {
+        &self.tile_map
+    }
+
+    /// Sprites drawn for objects, by asset name
+    #[allow(dead_code)]
+    pub fn object_map(&self) -> &HashMap<String, Pixmap> {
+        &self.object_map
+    }
+
+    /// Number of times the static terrain layer has been rasterised rather than reused from the cache
+    #[allow(dead_code)]
+    pub fn terrain_renders(&self) -> usize {
+        self.terrain_renders
+    }
+
+    /// Advance every aircraft by `dt` in fixed steps of 1 / `settings.simulation_frequency`, carrying any remainder
+    /// over to the next call so the physics is independent of how often step is called
+    /// # Arguments
+    /// * `dt` - elapsed time since the last call [s]
+    ///
+    /// Returns the number of fixed steps taken.
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) -> usize {
+
+        let fixed_dt = 1.0 / self.settings.simulation_frequency;
+        self.accumulator += dt;
+
+        // Small tolerance so a remainder that is a whole step up to rounding isn't carried over
+        let steps = (self.accumulator / fixed_dt + 1e-9).floor() as usize;
+        self.accumulator = (self.accumulator - steps as f64 * fixed_dt).max(0.0);
+
+        for _ in 0..steps {
+            for vehicle in self.vehicles.iter_mut() {
+                vehicle.step(fixed_dt);
+            }
+            self.sim_time += fixed_dt;
+            self.record_telemetry();
+        }
+
+        steps
+    }
+
+    /// Log the state of every aircraft to a CSV file after each fixed step
+    /// # Arguments
+    /// * `path` - location of the CSV file, an existing file is overwritten
+    #[allow(dead_code)]
+    pub fn enable_telemetry<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        self.telemetry = Some(Telemetry::new(path)?);
+        Ok(())
+    }
+
+    /// Stop logging and write out any buffered rows
+    #[allow(dead_code)]
+    pub fn disable_telemetry(&mut self) -> std::io::Result<()> {
+        match self.telemetry.take() {
+            Some(mut telemetry) => telemetry.flush(),
+            None => Ok(())
+        }
+    }
+
+    // Telemetry is dropped on a write error rather than failing the step
+    fn record_telemetry(&mut self) {
+        if let Some(telemetry) = self.telemetry.as_mut() {
+            let result = self.vehicles
+                .iter()
+                .enumerate()
+                .try_for_each(|(id, vehicle)| telemetry.record(self.sim_time, id, vehicle));
+            if let Err(err) = result {
+                eprintln!("telemetry disabled, failed to write: {}", err);
+                self.telemetry = None;
+            }
+        }
+    }
+
+    /// Geodetic position of an aircraft, None if the world has no geodetic origin
+    /// # Arguments
+    /// * `id` - index of the aircraft in `vehicles`
+    #[allow(dead_code)]
+    pub fn geodetic_position(&self, id: usize) -> Option<Geodetic> {
+        self.geodetic_origin.map(|origin| origin.local_to_geodetic(self.vehicles[id].position()))
+    }
+
+    /// Collisions between the aircraft in the world and with the static objects on the map
+    #[allow(dead_code)]
+    pub fn collisions(&self) -> Vec<CollisionEvent> {
+        let mut events = collision::aircraft_collisions(&self.vehicles);
+        events.extend(collision::object_collisions(&self.vehicles, &self.objects, self.origin, self.scale));
+        events
     }
 
 }
 
 impl World {
 
+    const RENDER_BAND_HEIGHT: u32 = 64;  // height of the bands tiles are rasterised into in parallel [pixels]
+    const COCKPIT_FOV: f32 = 60.0 * (std::f32::consts::PI / 180.0);  // vertical field of view from the cockpit [rads]
+
     pub fn render(&mut self) -> Pixmap {
 
-        match self.render_type.as_str() {
-            "world" => self.world_render(),
+        self.record_trails();
+        let mut canvas = match self.render_type.as_str() {
+            "world" => self.overview_render(),
             "aircraft" => self.aircraft_render(),
             "aircraft_fixed" => self.fixed_aircraft_render(),
+            "cockpit" => self.cockpit_render(),
             _ => {
                 println!("{} not a recognized render type, using world render", self.render_type);
-                self.world_render()  // Use world render method
+                self.overview_render()  // Use world render method
             }
-        }
+        };
+        self.apply_lighting(&mut canvas);
+        canvas
     } 
 
+    /// World render with supersampling and the minimap overlay applied at the output resolution
+    fn overview_render(&mut self) -> Pixmap {
+
+        // Limit the supersampled frame to 16 times the screen area, larger factors quickly exhaust memory
+        let factor = match self.anti_aliasing {
+            0 | 1 => 1,
+            2 | 3 => 2,
+            _ => 4
+        };
+        let mut canvas = if factor > 1 {
+            // Render at a multiple of the screen size then box filter back down
+            let screen_dims = self.screen_dims;
+            self.screen_dims = screen_dims * factor as f32;
+            let canvas = self.world_render();
+            self.screen_dims = screen_dims;
+            downsample(&canvas, factor)
+        } else {
+            self.world_render()
+        };
+
+        if self.minimap_dims[0] >= 1.0 && self.minimap_dims[1] >= 1.0 {
+            let minimap = self.minimap(self.minimap_dims[0] as u32, self.minimap_dims[1] as u32);
+            canvas.draw_pixmap(self.minimap_pos[0] as i32, self.minimap_pos[1] as i32, minimap.as_ref(), &PixmapPaint::default(), Transform::identity(), None);
+        }
+        canvas
+    }
+
+    /// Sine of the sun's elevation, 1.0 at noon and -1.0 at midnight
+    fn sun_elevation(&self) -> f32 {
+        let hour_angle = (self.time_of_day - 12.0) * (std::f32::consts::PI / 12.0);
+        hour_angle.cos()
+    }
+
+    /// Darken and blue-shift the canvas as the sun drops towards and below the horizon
+    fn apply_lighting(&self, canvas: &mut Pixmap) {
+        // Full daylight until the sun is 30 degrees up, full night once it is below the horizon
+        let darkness = (1.0 - self.sun_elevation() / 0.5).clamp(0.0, 1.0);
+        if darkness <= 0.0 {
+            return;
+        }
+        let mut night_paint = Paint::default();
+        night_paint.set_color_rgba8(10, 20, 60, (darkness * 180.0) as u8);
+        let rect = Rect::from_xywh(0.0, 0.0, canvas.width() as f32, canvas.height() as f32).unwrap();
+        canvas.fill_rect(rect, &night_paint, Transform::identity(), None);
+    }
+
     fn world_render(&mut self) -> Pixmap {
-        // Create the canvas to render onto
-        let mut canvas = Pixmap::new(self.screen_dims[0] as u32, self.screen_dims[1] as u32).unwrap();
+
+        // Reuse the static terrain layer unless the view or the terrain has changed since it was drawn
+        let key = TerrainLayerKey {
+            camera: [self.camera.x, self.camera.y, self.camera.z, self.camera.f],
+            screen_dims: self.screen_dims,
+            scale: self.scale,
+            time_of_day: self.time_of_day,
+            shadow_opacity: self.shadow_opacity,
+            fog_density: self.fog_density,
+            fog_colour: self.fog_colour
+        };
+        let mut canvas = match &self.terrain_layer {
+            Some((cached_key, layer)) if *cached_key == key => layer.clone(),
+            _ => {
+                let layer = self.terrain_render();
+                self.terrain_layer = Some((key, layer.clone()));
+                layer
+            }
+        };
+
+        let reconstruction_ratio = self.camera.f * self.camera.z;
+        let scaling_ratio = Vec2::new(
+            self.screen_dims[0] / reconstruction_ratio as f32,
+            self.screen_dims[1]/ reconstruction_ratio as f32
+        );
+
+        // Trails under the aircraft
+        for trail in self.trails.iter() {
+            let points: Vec<Vec2> = trail.iter().rev().map(|pos| self.world_to_screen(pos.truncate())).collect();
+            stroke_trail(&mut canvas, &points, self.trail_length, None);
+        }
+
+        // Render aircraft on top, sized to span a single tile, lowest first so higher aircraft are drawn over them
+        if let Some(sprite) = self.object_map.get("t67h") {
+            let sprite_scale = self.scale * scaling_ratio[0] / sprite.width() as f32;
+            let mut altitude_sorted: Vec<&Aircraft> = self.vehicles.iter().collect();
+            altitude_sorted.sort_by(|a, b| b.position()[2].total_cmp(&a.position()[2]));  // z is down
+            for vehicle in altitude_sorted {
+                let position = vehicle.position();
+                let pix_pos = self.world_to_screen(Vec2::new(position[0] as f32, position[1] as f32));
+                self.draw_aircraft(&mut canvas, vehicle, pix_pos, sprite_scale);
+            }
+        }
+        canvas
+    }
+
+    /// Pixel a point on the ground [m] in the world frame appears at in the world render
+    fn world_to_screen(&self, pos: Vec2) -> Vec2 {
+        let reconstruction_ratio = (self.camera.f * self.camera.z) as f32;
+        let camera = Vec2::new(self.camera.x as f32, self.camera.y as f32);
+        (pos - camera) * self.screen_dims / reconstruction_ratio + self.screen_dims / 2.0
+    }
+
+    /// Render the static tiles, objects and runway
+    fn terrain_render(&mut self) -> Pixmap {
+        self.terrain_renders += 1;
         let paint = PixmapPaint::default();
 
         // Calcuate the center of the screen and how much to transform each pixel by
@@ -302,8 +625,10 @@ impl World {
             self.screen_dims[1]/ reconstruction_ratio as f32
         );
 
+        let camera_height = self.camera.z as f32;
+
         // Render tiles
-        let render_results: Vec<(Pixmap, Transform)> = self.tiles.par_iter().filter_map(|tile: &Tile| {
+        let render_results: Vec<(Pixmap, Transform, f32)> = self.tiles.par_iter().filter_map(|tile: &Tile| {
             let pos = Vec2::new(tile.pos[0] - center[0], tile.pos[1] - center[1]);
             let pix_pos = pos * scaling_ratio;
             let pix_pos = pix_pos + self.screen_dims/2.0;
@@ -314,18 +639,47 @@ impl World {
                 && pix_pos[1] < self.screen_dims[1]+50.0 {
                     let tile = &self.tile_map[&tile.asset];
                     let transform = Transform::from_row(scale[0]/16.0, 0.0, 0.0, scale[1]/16.0, pix_pos[0], pix_pos[1]);
-                    Some((tile.clone(), transform))
+                    Some((tile.clone(), transform, fog_factor(pos, camera_height, self.fog_density)))
                 } else {
                     None
                 }
-        }).collect::<Vec<(Pixmap, Transform)>>();
+        }).collect::<Vec<(Pixmap, Transform, f32)>>();
         
-        for (pixmap, transform) in render_results {
-            canvas.draw_pixmap(0, 0, pixmap.as_ref(), &paint, transform, None);
+        // Create the canvas to render onto, rasterising the tiles into horizontal bands in parallel
+        let (width, height) = (self.screen_dims[0] as u32, self.screen_dims[1] as u32);
+        let mut canvas = rasterise_tiles(width, height, &render_results, self.fog_colour, Self::RENDER_BAND_HEIGHT);
+
+        // Render object shadows above the tiles but below the objects, cast away from the sun
+        let sun_elevation = self.sun_elevation();
+        if sun_elevation > 0.0 && self.shadow_opacity > 0.0 {
+            let hour_angle = (self.time_of_day - 12.0) * (std::f32::consts::PI / 12.0);
+            let shadow_length = (0.25 / sun_elevation).min(2.0);  // in object widths
+            // Screen x is north and y is east, the sun swings from east to west through the south
+            let shadow_offset = Vec2::new(0.5, hour_angle.sin()) * shadow_length;
+            let shadow_paint = PixmapPaint {
+                opacity: self.shadow_opacity,
+                ..PixmapPaint::default()
+            };
+            for object in self.objects.iter() {
+                let pos = Vec2::new(object.pos[0] - center[0], object.pos[1] - center[1]);
+                let pix_pos = pos * scaling_ratio + self.screen_dims/2.0;
+                let scale = self.scale * scaling_ratio;
+                let pix_pos = pix_pos + shadow_offset * scale;
+                if -50.0 < pix_pos[0]
+                    && -50.0 < pix_pos[1]
+                    && pix_pos[0] < self.screen_dims[0]+50.0
+                    && pix_pos[1] < self.screen_dims[1]+50.0 {
+                        let shadow = &self.shadow_map[&object.asset];
+                        let transform = Transform::from_row(scale[0]/16.0, 0.0, 0.0, scale[1]/16.0, pix_pos[0], pix_pos[1]);
+                        canvas.draw_pixmap(0, 0, shadow.as_ref(), &shadow_paint, transform, None);
+                    }
+            }
         }
 
-        // Render objects 
-        let render_results = self.objects.par_iter().filter_map(|object: &StaticObject| {
+        // Render objects, back to front so objects lower on the screen are drawn over those behind them
+        let mut depth_sorted: Vec<&StaticObject> = self.objects.iter().collect();
+        depth_sorted.sort_by(|a, b| a.pos[1].total_cmp(&b.pos[1]));
+        let render_results = depth_sorted.par_iter().filter_map(|object: &&StaticObject| {
             let pos = Vec2::new(object.pos[0] - center[0], object.pos[1] - center[1]);
             let pix_pos = pos * scaling_ratio;
             let pix_pos = pix_pos + self.screen_dims/2.0;
@@ -336,14 +690,19 @@ impl World {
                 && pix_pos[1] < self.screen_dims[1]+50.0 {
                     let object = &self.object_map[&object.asset];
                     let transform: Transform = Transform::from_row(scale[0]/16.0, 0.0, 0.0, scale[1]/16.0, pix_pos[0], pix_pos[1]);
-                    Some((object.clone(), transform))
+                    Some((object.clone(), transform, fog_factor(pos, camera_height, self.fog_density)))
                 } else {
                     None
                 }
-        }).collect::<Vec<(Pixmap, Transform)>>();
+        }).collect::<Vec<(Pixmap, Transform, f32)>>();
 
-        for (pixmap, transform) in render_results {
-            canvas.draw_pixmap(0, 0, pixmap.as_ref(), &paint, transform, None);
+        // Objects fade into the already fogged ground beneath them
+        for (pixmap, transform, fog) in render_results {
+            let object_paint = PixmapPaint {
+                opacity: 1.0 - fog,
+                ..PixmapPaint::default()
+            };
+            canvas.draw_pixmap(0, 0, pixmap.as_ref(), &object_paint, transform, None);
         }
 
         // Render runway if available
@@ -379,6 +738,83 @@ impl World {
         canvas
     }
 
+    /// Render a top-down overview of the whole map with a single colour per tile, marking the runway and aircraft
+    /// # Arguments
+    /// * `width` - width of the minimap [pixels], zero is treated as one
+    /// * `height` - height of the minimap [pixels], zero is treated as one
+    pub fn minimap(&self, width: u32, height: u32) -> Pixmap {
+
+        let (width, height) = (width.max(1), height.max(1));
+
+        // Draw one pixel per tile then stretch it to fit the minimap
+        let mut tile_pixmap = Pixmap::new((self.area[0] as u32).max(1), (self.area[1] as u32).max(1)).unwrap();
+        let tile_width = tile_pixmap.width() as usize;
+        for tile in self.tiles.iter() {
+            let idx = (tile.pos[0] / self.scale).round() as usize;
+            let idy = (tile.pos[1] / self.scale).round() as usize;
+            if idx < self.area[0] && idy < self.area[1] {
+                tile_pixmap.pixels_mut()[idy * tile_width + idx] = minimap_colour(&tile.name).premultiply();
+            }
+        }
+
+        let mut minimap = Pixmap::new(width, height).unwrap();
+        let scaling_ratio = Vec2::new(width as f32 / self.area[0] as f32, height as f32 / self.area[1] as f32);
+        let transform = Transform::from_scale(scaling_ratio[0], scaling_ratio[1]);
+        minimap.draw_pixmap(0, 0, tile_pixmap.as_ref(), &PixmapPaint::default(), transform, None);
+
+        // Convert a world position [m] into a minimap pixel
+        let to_pixel = |pos: Vec2| (pos + self.origin) / self.scale * scaling_ratio;
+
+        if let Some(runway) = &self.runway {
+            let half_length = Vec2::from_angle(runway.heading.to_radians()) * (runway.dims[1] / 2.0);
+            let start = to_pixel(runway.pos - half_length);
+            let end = to_pixel(runway.pos + half_length);
+            let runway_path = {
+                let mut pb = PathBuilder::new();
+                pb.move_to(start[0], start[1]);
+                pb.line_to(end[0], end[1]);
+                pb.finish().unwrap()
+            };
+            let mut runway_paint = Paint::default();
+            runway_paint.set_color_rgba8(255, 255, 255, 255);
+            let stroke = Stroke {
+                width: 2.0,
+                ..Stroke::default()
+            };
+            minimap.stroke_path(&runway_path, &runway_paint, &stroke, Transform::identity(), None);
+        }
+
+        let mut aircraft_paint = Paint::default();
+        aircraft_paint.set_color_rgba8(255, 0, 0, 255);
+        aircraft_paint.anti_alias = true;
+        for vehicle in self.vehicles.iter() {
+            let position = vehicle.position();
+            let pix_pos = to_pixel(Vec2::new(position[0] as f32, position[1] as f32));
+            if let Some(marker) = PathBuilder::from_circle(pix_pos[0], pix_pos[1], 2.0) {
+                minimap.fill_path(&marker, &aircraft_paint, FillRule::Winding, Transform::identity(), None);
+            }
+        }
+
+        minimap
+    }
+
+    /// Draw the aircraft sprite centred on `pix_pos` and rotated to the aircraft's heading, nothing is drawn if the
+    /// sprite hasn't been loaded
+    fn draw_aircraft(&self, canvas: &mut Pixmap, vehicle: &Aircraft, pix_pos: Vec2, scale: f32) {
+        let sprite = match self.object_map.get("t67h") {
+            Some(sprite) => sprite,
+            None => return
+        };
+        let heading = quat_to_euler(&vehicle.attitude()).2;
+        let transform = Transform::from_row(
+            scale, 0.0, 0.0, scale,
+            pix_pos.x - (sprite.width() as f32 * scale / 2.0),
+            pix_pos.y - (sprite.height() as f32 * scale / 2.0)
+        );
+        let transform = transform.post_rotate_at((heading as f32 * 180.0 / std::f32::consts::PI) + 90.0, pix_pos.x, pix_pos.y);
+        canvas.draw_pixmap(0, 0, sprite.as_ref(), &PixmapPaint::default(), transform, None);
+    }
+
     fn aircraft_render(&mut self) -> Pixmap {
 
         let now = Instant::now();
@@ -415,7 +851,7 @@ impl World {
         // TODO: Zoom out on aircraft
         // Add planar aircraft
         let horizontal_object = &self.object_map["t67h"];
-        let heading = self.vehicles[0].attitude().euler_angles().2;
+        let heading = quat_to_euler(&self.vehicles[0].attitude()).2;
         let horizontal_pixel_x_pos = horizontal_screen_center.x - horizontal_object.width() as f32 /2.0;
         let horizontal_pixel_y_pos= horizontal_screen_center.y - horizontal_object.height() as f32 /2.0;
         let horizontal_transform = Transform::from_row(1.0, 0.0, 0.0, 1.0, horizontal_pixel_x_pos, horizontal_pixel_y_pos);
@@ -423,50 +859,39 @@ impl World {
         
         // Add vertical aircraft
         let vertical_object = &self.object_map["t67v"];
-        let pitch = -1.0 * self.vehicles[0].attitude().euler_angles().1;
+        let pitch = -1.0 * quat_to_euler(&self.vehicles[0].attitude()).1;
         let vertical_transform = Transform::from_row(1.0, 0.0, 0.0, 1.0, vertical_screen_center.x - vertical_object.width() as f32 /2.0, vertical_screen_center.y - vertical_object.height() as f32 /2.0);
         let vertical_transform = vertical_transform.post_rotate_at(pitch as f32 * 180.0 / std::f32::consts::PI, vertical_screen_center.x, vertical_screen_center.y);
         
         let t_aircraft_setup = now.elapsed();
         println!("Aircraft Setup: {:?}", t_aircraft_setup);
 
-        // Add position traces to track aircraft
-        let mut trace_paint = Paint::default();
-        trace_paint.set_color_rgba8(255, 0, 0, 200);
-        trace_paint.anti_alias = true;
-        let mut idt = 0.0;
-        for pos in self.pos_log.iter().rev() {
-            idt += 1.0;
-            // println!("x: {}, y: {}, z: {}", pos.x, pos.y, pos.z);
-            let horizontal_path_x_pos = horizontal_screen_center.x - (self.camera.y as f32 - pos.y);
-            let horizontal_path_y_pos = horizontal_screen_center.y - (pos.x - self.camera.x as f32); 
-            // Stop line breaking outside split point
-            if horizontal_path_y_pos < split_point {
-                let horizontal_point = PathBuilder::from_circle(
-                    horizontal_path_x_pos,
-                    horizontal_path_y_pos,
-                    3.0).unwrap();
-                canvas.fill_path(&horizontal_point, &trace_paint, FillRule::Winding, Transform::identity(), None);
-            }
-            // println!("self.camera.z: {}, pos.z: {}, idt: {}", self.camera.z, pos.z, idt);
-            let horizontal_path_y_pos = (self.camera.z as f32 - pos.z) + vertical_screen_center.y;
-            if horizontal_path_y_pos > split_point {
-                let vertical_point = PathBuilder::from_circle(
-                    vertical_screen_center.x - idt,
-                    horizontal_path_y_pos,
-                    3.0).unwrap();
-                canvas.fill_path(&vertical_point, &trace_paint, FillRule::Winding, Transform::identity(), None);
-            }
+        // Add the trail of the aircraft, above it in the top view and trailing behind it in the side view
+        if let Some(trail) = self.trails.first() {
+            let current = self.vehicles[0].position();
+            let plan_points: Vec<Vec2> = trail.iter().rev().map(|pos| Vec2::new(
+                horizontal_screen_center.x - (current[1] as f32 - pos.y),
+                horizontal_screen_center.y - (pos.x - current[0] as f32)
+            )).collect();
+            let side_points: Vec<Vec2> = trail.iter().rev().enumerate().map(|(age, pos)| Vec2::new(
+                vertical_screen_center.x - age as f32,
+                vertical_screen_center.y + (pos.z - current[2] as f32)
+            )).collect();
+
+            // Keep each trail inside its half of the split screen
+            let (width, height) = (canvas.width(), canvas.height());
+            let half_mask = |top: f32, bottom: f32| {
+                let mut mask = Mask::new(width, height).unwrap();
+                let half = PathBuilder::from_rect(Rect::from_ltrb(0.0, top, self.screen_dims[0], bottom).unwrap());
+                mask.fill_path(&half, FillRule::Winding, false, Transform::identity());
+                mask
+            };
+            stroke_trail(&mut canvas, &plan_points, self.trail_length, Some(&half_mask(0.0, split_point)));
+            stroke_trail(&mut canvas, &side_points, self.trail_length, Some(&half_mask(split_point, self.screen_dims[1])));
         }
 
         let t_path_setup = now.elapsed();
         println!("Path Setup: {:?}", t_path_setup);
-        // Add current position to pos_log
-        self.pos_log.push(Vec3::new(self.camera.x as f32, self.camera.y as f32, self.camera.z as f32));
-        // Save Some memory by dropping points once outside viewport, 400 seems enough
-        if self.pos_log.len() > 400 {
-            self.pos_log.remove(0);
-        }
 
         // println!("heading: {:?}", self.vehicles[0].aff_body.body.attitude().euler_angles());
         // println!("center: {}", screen_center);
@@ -481,11 +906,143 @@ impl World {
         canvas
     }
 
+    /// Forward view from the aircraft's cockpit
+    ///
+    /// Each pixel's ray is rotated by the aircraft's full attitude and traced to the flat ground at z = 0, where it
+    /// takes the colour of the runway or tile it lands on, rays that never reach the ground show the sky. Static
+    /// objects stand upright on the ground as billboards the size of a tile, drawn farthest first, and both fade into
+    /// the fog with distance. Without an aircraft to look out of this falls back to the world render.
+    fn cockpit_render(&mut self) -> Pixmap {
+
+        if self.vehicles.is_empty() {
+            return self.world_render();
+        }
+
+        let (width, height) = (self.screen_dims[0] as u32, self.screen_dims[1] as u32);
+        let mut canvas = Pixmap::new(width, height).unwrap();
+        let screen_center = self.screen_dims / 2.0;
+        let focal_length = screen_center.y / (Self::COCKPIT_FOV / 2.0).tan();  // [pixels]
+
+        let vehicle = &self.vehicles[0];
+        let position = vehicle.position();
+        let eye = Vec3::new(position[0] as f32, position[1] as f32, position[2] as f32);
+        let altitude = -eye.z;  // z is down
+        let dcm: Vec<f32> = quat_to_dcm(&vehicle.attitude()).iter().map(|&v| v as f32).collect();
+        let body_to_world = Mat3::from_cols_slice(&dcm);
+        let roll = quat_to_euler(&vehicle.attitude()).0 as f32;
+
+        // Tile sprites laid out on the map grid so a ground point can find its tile directly
+        let mut tile_grid: Vec<Option<&Pixmap>> = vec![None; self.area[0] * self.area[1]];
+        for tile in self.tiles.iter() {
+            let idx = (tile.pos[0] / self.scale).round() as usize;
+            let idy = (tile.pos[1] / self.scale).round() as usize;
+            if idx < self.area[0] && idy < self.area[1] {
+                tile_grid[idy * self.area[0] + idx] = self.tile_map.get(&tile.asset);
+            }
+        }
+        let runway = self.runway.as_ref().and_then(|runway| Some((runway, self.object_map.get(&runway.asset)?)));
+
+        let sky = ColorU8::from_rgba(110, 160, 220, 255);
+        let grass = minimap_colour("Grass");
+        let (area, scale, origin, fog_density, fog_colour) = (&self.area, self.scale, self.origin, self.fog_density, self.fog_colour);
+
+        // Colour of the ground at a point in the world frame [m]
+        let ground_colour = |point: Vec2| -> ColorU8 {
+            if let Some((runway, pixmap)) = runway {
+                let located = runway.locate(point);
+                if located.region == RunwayRegion::OnRunway {
+                    let tx = ((located.lateral / runway.dims[0] + 0.5) * pixmap.width() as f32) as u32;
+                    let ty = ((located.longitudinal / runway.dims[1] + 0.5) * pixmap.height() as f32) as u32;
+                    return pixmap.pixel(tx.min(pixmap.width() - 1), ty.min(pixmap.height() - 1)).unwrap().demultiply();
+                }
+            }
+            let map = (point + origin) / scale;
+            if map.x < 0.0 || map.y < 0.0 || map.x >= area[0] as f32 || map.y >= area[1] as f32 {
+                return grass;
+            }
+            match tile_grid[map.y as usize * area[0] + map.x as usize] {
+                Some(pixmap) => {
+                    let tx = (map.x.fract() * pixmap.width() as f32) as u32;
+                    let ty = (map.y.fract() * pixmap.height() as f32) as u32;
+                    pixmap.pixel(tx, ty).unwrap().demultiply()
+                },
+                None => grass
+            }
+        };
+
+        canvas.pixels_mut().par_chunks_mut(width as usize).enumerate().for_each(|(row, pixels)| {
+            for (column, pixel) in pixels.iter_mut().enumerate() {
+                // Body axes are x forward, y right and z down, matching the screen's right and down
+                let ray = body_to_world * Vec3::new(focal_length, column as f32 + 0.5 - screen_center.x, row as f32 + 0.5 - screen_center.y);
+                let colour = if ray.z <= 0.0 {
+                    sky
+                } else {
+                    let reach = ray * (altitude / ray.z).max(0.0);
+                    let ground = ground_colour(Vec2::new(eye.x + reach.x, eye.y + reach.y));
+                    let fog = fog_factor(Vec2::new(reach.x, reach.y), altitude, fog_density);
+                    let blend = |c: u8, f: u8| (c as f32 * (1.0 - fog) + f as f32 * fog) as u8;
+                    ColorU8::from_rgba(
+                        blend(ground.red(), fog_colour[0]),
+                        blend(ground.green(), fog_colour[1]),
+                        blend(ground.blue(), fog_colour[2]),
+                        255
+                    )
+                };
+                *pixel = colour.premultiply();
+            }
+        });
+
+        // Objects in front of the aircraft in its body frame, farthest first so nearer ones are drawn over them
+        let world_to_body = body_to_world.transpose();
+        let mut in_view: Vec<(Vec3, f32, &Pixmap)> = self.objects.iter().filter_map(|object| {
+            let centre = object.pos - self.origin + self.scale / 2.0;
+            let offset = Vec3::new(centre.x, centre.y, 0.0) - eye;
+            let base = world_to_body * offset;
+            let sprite = self.object_map.get(&object.asset)?;
+            (base.x > 1.0).then(|| (base, fog_factor(Vec2::new(offset.x, offset.y), altitude, fog_density), sprite))
+        }).collect();
+        in_view.sort_by(|a, b| b.0.x.total_cmp(&a.0.x));
+
+        for (base, fog, sprite) in in_view {
+            let size = focal_length * self.scale / base.x;  // [pixels]
+            let foot = Vec2::new(base.y, base.z) * (focal_length / base.x) + screen_center;
+            if size < 1.0 || foot.x < -size || foot.x > self.screen_dims[0] + size || foot.y < 0.0 || foot.y > self.screen_dims[1] + size {
+                continue;
+            }
+            let sprite_scale = size / sprite.width() as f32;
+            let transform = Transform::from_row(sprite_scale, 0.0, 0.0, sprite_scale, foot.x - size / 2.0, foot.y - sprite.height() as f32 * sprite_scale);
+            let transform = transform.post_rotate_at(-roll.to_degrees(), foot.x, foot.y);
+            let object_paint = PixmapPaint {
+                opacity: 1.0 - fog,
+                ..PixmapPaint::default()
+            };
+            canvas.draw_pixmap(0, 0, sprite.as_ref(), &object_paint, transform, None);
+        }
+
+        // Fixed aircraft reference symbol
+        let stroke = Stroke {
+            width: 2.0,
+            ..Stroke::default()
+        };
+        let mut symbol_paint = Paint::default();
+        symbol_paint.set_color_rgba8(255, 200, 0, 255);
+        let symbol_path = {
+            let mut pb = PathBuilder::new();
+            pb.move_to(screen_center.x - 40.0, screen_center.y);
+            pb.line_to(screen_center.x - 10.0, screen_center.y);
+            pb.move_to(screen_center.x + 10.0, screen_center.y);
+            pb.line_to(screen_center.x + 40.0, screen_center.y);
+            pb.finish().unwrap()
+        };
+        canvas.stroke_path(&symbol_path, &symbol_paint, &stroke, Transform::identity(), None);
+
+        canvas
+    }
+
     fn fixed_aircraft_render(&mut self) -> Pixmap {
         
         // Setup canvas
         let mut canvas = Pixmap::new(self.screen_dims[0] as u32, self.screen_dims[1] as u32).unwrap();
-        let pixmap_paint = PixmapPaint::default();
         let screen_center = self.screen_dims / 2.0;
         // let map_center = Vec2::new(self.area[0] as f32 / 2.0, self.area[1] as f32 / 2.0);
         let scale = Vec2::new(self.screen_dims[0] / (self.area[0] as f32 * 16.0), self.screen_dims[1] / (self.area[1] as f32 * 16.0));
@@ -493,16 +1050,9 @@ impl World {
         let ac_pix_y_pos = (self.camera.y as f32 * scale.y) + screen_center.y;
         
         // Add aircraft
-        let aircraft = &self.object_map["t67h"];
-        let heading = self.vehicles[0].attitude().euler_angles().2;
-        let aircraft_pixel_x_pos = ac_pix_x_pos - (aircraft.width() as f32 / 2.0);
-        let aircraft_pixel_y_pos= ac_pix_y_pos - (aircraft.height() as f32 / 2.0);
         println!("camera: {}, {}", self.camera.x, self.camera.y);
         println!("scale: {}, {}, area: {:?}", scale.x, scale.y, self.area);
-        println!("horizontal_pixel_x_pos: {}, horizontal_pixel_y_pos: {}", aircraft_pixel_x_pos, aircraft_pixel_y_pos);
-        let horizontal_transform = Transform::from_row(1.0, 0.0, 0.0, 1.0, aircraft_pixel_x_pos, aircraft_pixel_y_pos);
-        let horizontal_transform = horizontal_transform.post_rotate_at((heading as f32 * 180.0 / std::f32::consts::PI) + 90.0, aircraft_pixel_x_pos, aircraft_pixel_y_pos);
-        canvas.draw_pixmap(0, 0, aircraft.as_ref(), &pixmap_paint, horizontal_transform, None);
+        self.draw_aircraft(&mut canvas, &self.vehicles[0], Vec2::new(ac_pix_x_pos, ac_pix_y_pos), 1.0);
 
         // Add goal if present
         match &self.goal {
@@ -525,12 +1075,21 @@ impl World {
 
     }
 
-    // pub fn get_image(&mut self) -> Vec<u8> {
+    /// Render the world and return the frame as a raw RGB buffer
+    ///
+    /// Pixels are stored row by row from the top-left corner, each as 3 bytes in R, G, B order with alpha
+    /// removed, so the buffer is `width * height * 3` bytes long with a stride of `width * 3`.
+    #[allow(dead_code)]
+    pub fn get_image(&mut self) -> Vec<u8> {
 
-    //     let rect = Rect::new(0, 0, 1024, 1024);
-    //     self.canvas.read_pixels(rect, PixelFormatEnum::RGB24).unwrap()
-    
-    // }
+        let pixmap = self.render();
+        let mut image = Vec::with_capacity((pixmap.width() * pixmap.height() * 3) as usize);
+        for pixel in pixmap.pixels() {
+            let colour = pixel.demultiply();
+            image.extend_from_slice(&[colour.red(), colour.green(), colour.blue()]);
+        }
+        image
+    }
 
 }
 
@@ -539,7 +1098,9 @@ pub struct Camera {
     pub x: f64,  // camera's x-position
     pub y: f64,  // camera's y-position
     pub z: f64,  // camera's z-position
-    pub f: f64,  // camera's reconstruction ratio/zoom
+    f: f64,  // camera's reconstruction ratio/zoom, only set through set_zoom so it stays within its limits
+    pub stiffness: f64,  // natural frequency of the follow spring [rad/s]
+    velocity: [f64; 3]  // camera's velocity while following
 }
 
 impl Default for Camera{
@@ -549,13 +1110,18 @@ impl Default for Camera{
             x: 0.0,
             y: 0.0, 
             z: 5000.0,
-            f: 1.0
+            f: 1.0,
+            stiffness: 2.0,
+            velocity: [0.0; 3]
         }
     }
 }
 
 impl Camera {
 
+    const MIN_ZOOM: f64 = 0.01;
+    const MAX_ZOOM: f64 = 100.0;
+
     #[allow(dead_code)]
     fn new(
         x: f64,
@@ -565,7 +1131,7 @@ impl Camera {
     ) -> Self {
 
         let f = if let Some(focal_dist) = f {
-            focal_dist
+            focal_dist.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM)
         } else {
             1.0
         };
@@ -574,7 +1140,9 @@ impl Camera {
             x,
             y,
             z,
-            f
+            f,
+            stiffness: 2.0,
+            velocity: [0.0; 3]
         }
 
     }
@@ -583,6 +1151,47 @@ impl Camera {
         self.x = pos[0];
         self.y = pos[1];
         self.z = -1.0 * pos[2];
+        self.velocity = [0.0; 3];
+    }
+
+    /// Move the camera towards `pos` on a critically damped spring rather than snapping to it
+    /// # Arguments
+    /// * `pos` - position to follow, in the same frame as [Camera::move_camera]
+    /// * `dt` - time since the last update [s]
+    ///
+    /// Uses the exact solution of the spring over `dt`, so the path is the same whatever the update rate.
+    #[allow(dead_code)]
+    pub fn follow(&mut self, pos: Vec<f64>, dt: f64) {
+        let target = [pos[0], pos[1], -pos[2]];
+        let current = [self.x, self.y, self.z];
+        let omega = self.stiffness;
+        let decay = (-omega * dt).exp();
+
+        let mut next = [0.0; 3];
+        for axis in 0..3 {
+            let offset = current[axis] - target[axis];
+            let velocity = self.velocity[axis];
+            let damping = velocity + omega * offset;
+            next[axis] = target[axis] + (offset + damping * dt) * decay;
+            self.velocity[axis] = (velocity - omega * damping * dt) * decay;
+        }
+
+        self.x = next[0];
+        self.y = next[1];
+        self.z = next[2];
+    }
+
+    /// Set the zoom, which scales the field of view so doubling it halves on-screen distances
+    /// # Arguments
+    /// * `zoom` - new zoom, clamped to [MIN_ZOOM, MAX_ZOOM]
+    #[allow(dead_code)]
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.f = zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+    }
+
+    #[allow(dead_code)]
+    pub fn zoom(&self) -> f64 {
+        self.f
     }
 
 }
@@ -638,3 +1247,673 @@ impl Settings {
     }
 
 }
+
+/// Fraction of haze between a camera at `camera_height` [m] and a point `pos` [m] on the ground relative to the camera
+fn fog_factor(pos: Vec2, camera_height: f32, density: f32) -> f32 {
+    if density <= 0.0 {
+        return 0.0;
+    }
+    let distance = (pos.length_squared() + camera_height * camera_height).sqrt();
+    1.0 - (-density * distance).exp()
+}
+
+/// Draw tiles onto a new `width` x `height` pixmap, rasterising horizontal bands of `band_height` rows in parallel
+/// and then copying the bands into place
+/// # Arguments
+/// * `tiles` - tile sprite, its transform onto the pixmap and the fraction of haze over it
+/// * `fog_colour` - colour of the haze
+/// * `band_height` - rows per band, the output is identical for any height so this only trades overhead for parallelism
+fn rasterise_tiles(width: u32, height: u32, tiles: &[(Pixmap, Transform, f32)], fog_colour: [u8; 3], band_height: u32) -> Pixmap {
+
+    let paint = PixmapPaint::default();
+    let band_starts: Vec<u32> = (0..height).step_by(band_height as usize).collect();
+    let bands: Vec<(u32, Pixmap)> = band_starts.par_iter().map(|&band_y| {
+        let band_height = band_height.min(height - band_y);
+        let mut band = Pixmap::new(width, band_height).unwrap();
+        let tile_rect = Rect::from_xywh(0.0, 0.0, 16.0, 16.0).unwrap();
+        // Tiles are drawn without anti-aliasing so the haze must be too, or it thins at fractional tile edges
+        let mut fog_paint = Paint {
+            anti_alias: false,
+            ..Paint::default()
+        };
+        for (pixmap, transform, fog) in tiles.iter() {
+            // Skip tiles that lie entirely outside of this band
+            if transform.ty + transform.sy * 16.0 < band_y as f32 || transform.ty > (band_y + band_height) as f32 {
+                continue;
+            }
+            let transform = transform.post_translate(0.0, -(band_y as f32));
+            band.draw_pixmap(0, 0, pixmap.as_ref(), &paint, transform, None);
+            if *fog > 0.0 {
+                fog_paint.set_color_rgba8(fog_colour[0], fog_colour[1], fog_colour[2], (fog * 255.0) as u8);
+                band.fill_rect(tile_rect, &fog_paint, transform, None);
+            }
+        }
+        (band_y, band)
+    }).collect();
+
+    let mut canvas = Pixmap::new(width, height).unwrap();
+    let row_bytes = width as usize * 4;
+    for (band_y, band) in bands {
+        let start = band_y as usize * row_bytes;
+        canvas.data_mut()[start..start + band.data().len()].copy_from_slice(band.data());
+    }
+    canvas
+}
+
+/// Colour used to represent each tile type on the minimap
+fn minimap_colour(tile_name: &str) -> ColorU8 {
+    match tile_name {
+        "Grass" => ColorU8::from_rgba(86, 152, 54, 255),
+        "Forest" => ColorU8::from_rgba(34, 90, 40, 255),
+        "Crops" => ColorU8::from_rgba(139, 105, 60, 255),
+        "Orchard" => ColorU8::from_rgba(110, 170, 70, 255),
+        "Water" => ColorU8::from_rgba(50, 100, 200, 255),
+        "Sand" => ColorU8::from_rgba(220, 200, 130, 255),
+        _ => ColorU8::from_rgba(128, 128, 128, 255)
+    }
+}
+
+/// Shrink a pixmap by an integer factor, averaging each factor x factor block of pixels
+fn downsample(pixmap: &Pixmap, factor: u32) -> Pixmap {
+    let width = pixmap.width() / factor;
+    let height = pixmap.height() / factor;
+    let mut small = Pixmap::new(width, height).unwrap();
+    let samples = factor * factor;
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 4];
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    let pixel = pixmap.pixel(x * factor + dx, y * factor + dy).unwrap();
+                    sum[0] += pixel.red() as u32;
+                    sum[1] += pixel.green() as u32;
+                    sum[2] += pixel.blue() as u32;
+                    sum[3] += pixel.alpha() as u32;
+                }
+            }
+            let average = sum.map(|c| (c / samples) as u8);
+            small.pixels_mut()[(y * width + x) as usize] = PremultipliedColorU8::from_rgba(average[0], average[1], average[2], average[3]).unwrap();
+        }
+    }
+    small
+}
+
+/// Stroke a trail as a polyline whose segments fade out with age
+/// # Arguments
+/// * `points` - screen positions along the trail [pixels], newest first
+/// * `trail_length` - number of points in a full trail, the oldest segment of a full trail is nearly transparent
+/// * `mask` - area of the canvas the trail may be drawn in, None for all of it
+fn stroke_trail(canvas: &mut Pixmap, points: &[Vec2], trail_length: usize, mask: Option<&Mask>) {
+    let stroke = Stroke {
+        width: 2.0,
+        line_cap: LineCap::Round,
+        ..Stroke::default()
+    };
+    let mut paint = Paint::default();
+    paint.anti_alias = true;
+    for (age, segment) in points.windows(2).enumerate() {
+        let alpha = 200.0 * (1.0 - age as f32 / trail_length.max(1) as f32);
+        paint.set_color_rgba8(255, 0, 0, alpha as u8);
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.move_to(segment[0].x, segment[0].y);
+            pb.line_to(segment[1].x, segment[1].y);
+            pb.finish()
+        };
+        if let Some(path) = path {
+            canvas.stroke_path(&path, &paint, &stroke, Transform::identity(), mask);
+        }
+    }
+}
+
+/// Black copy of a sprite that keeps its alpha, used to draw shadows
+fn silhouette(pixmap: &Pixmap) -> Pixmap {
+    let mut shadow = pixmap.clone();
+    for pixel in shadow.pixels_mut() {
+        *pixel = PremultipliedColorU8::from_rgba(0, 0, 0, pixel.alpha()).unwrap();
+    }
+    shadow
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerso::types::{UnitQuaternion, Vector3};
+
+    // Opaque square sprite of a single colour, the same size as the tile assets
+    fn sprite(r: u8, g: u8, b: u8) -> Pixmap {
+        let mut pixmap = Pixmap::new(16, 16).unwrap();
+        pixmap.fill(Color::from_rgba8(r, g, b, 255));
+        pixmap
+    }
+
+    // World covered in `area` x `area` grass tiles, with the camera 100 m above the middle so each tile spans 16
+    // pixels of the 64 x 64 frame
+    fn test_world(area: usize) -> World {
+        let mut world = World {
+            screen_dims: Vec2::new(64.0, 64.0),
+            area: vec![area, area],
+            ..World::default()
+        };
+        world.origin = Vec2::splat(world.scale * area as f32 / 2.0);
+        let tiles = (0..area)
+            .flat_map(|x| (0..area).map(move |y| (x, y)))
+            .map(|(x, y)| Tile {
+                name: "Grass".to_string(),
+                asset: "grass".to_string(),
+                pos: Vec2::new(x as f32, y as f32) * world.scale
+            })
+            .collect();
+        world.set_tiles(tiles);
+        world.set_tile_asset("grass", sprite(86, 152, 54));
+        world.camera.move_camera(vec![0.0, 0.0, -100.0]);
+        world
+    }
+
+    fn aircraft_at(x: f64, y: f64, z: f64, heading: f64) -> Aircraft {
+        Aircraft::new(
+            "TO",
+            Vector3::new(x, y, z),
+            Vector3::zeros(),
+            UnitQuaternion::from_euler_angles(0.0, 0.0, heading),
+            Vector3::zeros(),
+            None,
+            None
+        )
+    }
+
+    fn rgb(pixmap: &Pixmap, x: u32, y: u32) -> [u8; 3] {
+        let colour = pixmap.pixel(x, y).unwrap().demultiply();
+        [colour.red(), colour.green(), colour.blue()]
+    }
+
+    #[test]
+    fn world_without_aircraft_renders() {
+        let mut world = World {
+            screen_dims: Vec2::new(64.0, 64.0),
+            ..World::default()
+        };
+        let canvas = world.render();
+        assert_eq!((canvas.width(), canvas.height()), (64, 64));
+    }
+
+    #[test]
+    fn every_aircraft_is_drawn() {
+        let mut world = test_world(8);
+        world.set_object_asset("t67h", sprite(255, 0, 0));
+        for (x, z) in [(-30.0, -100.0), (0.0, -300.0), (30.0, -200.0)] {
+            world.add_aircraft(aircraft_at(x, 0.0, z, 0.0));
+        }
+        let canvas = world.render();
+
+        // Sprites span a 16 pixel tile and sit 19.2 pixels apart along the row through the screen centre
+        for x in [12, 32, 51] {
+            assert_eq!(rgb(&canvas, x, 32), [255, 0, 0], "no aircraft at x = {}", x);
+        }
+        for x in [22, 42] {
+            assert_eq!(rgb(&canvas, x, 32), [86, 152, 54], "aircraft sprites merged at x = {}", x);
+        }
+    }
+
+    fn mean_luminance(pixmap: &Pixmap) -> f32 {
+        let total: f32 = pixmap.pixels().iter().map(|pixel| {
+            let colour = pixel.demultiply();
+            0.2126 * colour.red() as f32 + 0.7152 * colour.green() as f32 + 0.0722 * colour.blue() as f32
+        }).sum();
+        total / pixmap.pixels().len() as f32
+    }
+
+    #[test]
+    fn midnight_is_darker_than_noon() {
+        let mut world = test_world(8);
+        world.time_of_day = 12.0;
+        let noon = mean_luminance(&world.render());
+        world.time_of_day = 0.0;
+        let midnight = mean_luminance(&world.render());
+        assert!(midnight < 0.5 * noon, "noon {} midnight {}", noon, midnight);
+    }
+
+    // Add a single tree sprite with its top-left corner at a world position [m]
+    fn add_tree(world: &mut World, pos: Vec2, colour: [u8; 3], asset: &str) {
+        world.set_object_asset(asset, sprite(colour[0], colour[1], colour[2]));
+        let mut objects = std::mem::take(&mut world.objects);
+        objects.push(StaticObject {
+            name: "Tree".to_string(),
+            asset: asset.to_string(),
+            pos: pos + world.origin
+        });
+        world.set_objects(objects);
+    }
+
+    #[test]
+    fn shadows_fall_away_from_the_sun() {
+        let mut world = test_world(8);
+        add_tree(&mut world, Vec2::ZERO, [30, 30, 200], "tree");
+        world.time_of_day = 9.0;
+
+        // Shadows are off by default
+        assert_eq!(rgb(&world.render(), 49, 36), [86, 152, 54]);
+
+        // At 09:00 the sun is in the south east, so the 16 pixel tree casts its shadow 2.8 pixels north (right) and
+        // 4 pixels west (up), under the tree itself
+        world.shadow_opacity = 0.5;
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 49, 36), [43, 76, 27]);
+        assert_eq!(rgb(&canvas, 40, 29), [43, 76, 27]);
+        assert_eq!(rgb(&canvas, 29, 45), [86, 152, 54]);
+        assert_eq!(rgb(&canvas, 40, 49), [86, 152, 54]);
+        assert_eq!(rgb(&canvas, 40, 40), [30, 30, 200]);
+
+        // At noon the sun is due south and the shadow falls due north
+        world.time_of_day = 12.0;
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 49, 40), [43, 76, 27]);
+        assert_eq!(rgb(&canvas, 40, 30), [86, 152, 54]);
+        assert_eq!(rgb(&canvas, 29, 40), [86, 152, 54]);
+    }
+
+    #[test]
+    fn aircraft_sprite_turns_with_heading() {
+        // Bar across the sprite so its orientation shows, drawn rotated a further 90 degrees to face up the screen
+        let mut bar = Pixmap::new(16, 16).unwrap();
+        bar.fill_rect(Rect::from_xywh(0.0, 6.0, 16.0, 4.0).unwrap(), &Paint::default(), Transform::identity(), None);
+
+        let render = |heading: f64| {
+            let mut world = test_world(8);
+            world.set_object_asset("t67h", bar.clone());
+            world.add_aircraft(aircraft_at(0.0, 0.0, -100.0, heading));
+            world.render()
+        };
+        let black = [0, 0, 0];
+
+        let north = render(0.0);
+        assert_eq!(rgb(&north, 32, 25), black);
+        assert_ne!(rgb(&north, 25, 32), black);
+
+        let east = render(std::f64::consts::FRAC_PI_2);
+        assert_eq!(rgb(&east, 25, 32), black);
+        assert_ne!(rgb(&east, 32, 25), black);
+    }
+
+    #[test]
+    fn minimap_marks_aircraft() {
+        let mut world = test_world(8);
+        world.add_aircraft(aircraft_at(0.0, 0.0, -100.0, 0.0));
+
+        // The aircraft is over the middle of the map
+        let minimap = world.minimap(40, 30);
+        assert_eq!((minimap.width(), minimap.height()), (40, 30));
+        assert_eq!(rgb(&minimap, 20, 15), [255, 0, 0]);
+        assert_eq!(rgb(&minimap, 2, 2), [86, 152, 54]);
+
+        // Overlaid on the world render in the requested corner
+        world.minimap_dims = Vec2::new(16.0, 16.0);
+        world.minimap_pos = Vec2::new(48.0, 0.0);
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 56, 8), [255, 0, 0]);
+        assert_eq!(rgb(&canvas, 8, 8), [86, 152, 54]);
+    }
+
+    #[test]
+    fn zero_size_minimap_is_a_pixel() {
+        let world = test_world(8);
+        for (width, height) in [(0, 0), (0, 30), (40, 0)] {
+            let minimap = world.minimap(width, height);
+            assert_eq!((minimap.width(), minimap.height()), (width.max(1), height.max(1)));
+        }
+        let empty = World { area: vec![0, 0], ..World::default() };
+        assert_eq!(empty.minimap(0, 0).width(), 1);
+    }
+
+    #[test]
+    fn trail_follows_flight_path() {
+        let mut world = World {
+            screen_dims: Vec2::new(200.0, 200.0),
+            render_type: "aircraft".to_string(),
+            ..World::default()
+        };
+        world.set_object_asset("t67h", Pixmap::new(1, 1).unwrap());
+        world.set_object_asset("t67v", Pixmap::new(1, 1).unwrap());
+        world.add_aircraft(aircraft_at(0.0, 0.0, -100.0, 0.0));
+
+        // Rows of the top panel's centre column the trail is drawn in, from the aircraft at row 66 to the split line
+        // at row 132
+        let trail_rows = |canvas: &Pixmap| -> Vec<u32> {
+            (66..128).filter(|&y| canvas.pixel(100, y).unwrap().alpha() > 0).collect()
+        };
+
+        // Fly straight and level at 5 m per frame, the trail runs back from the aircraft through every frame's position
+        let fly = |world: &mut World, frames: usize| {
+            let mut canvas = None;
+            for _ in 0..frames {
+                let x = world.vehicles[0].position()[0] + 5.0;
+                world.update_aircraft(aircraft_at(x, 0.0, -100.0, 0.0), 0);
+                canvas = Some(world.render());
+            }
+            canvas.unwrap()
+        };
+
+        // A 2 pixel wide line with round caps a pixel per metre, the oldest segment is faded so its end may not show
+        let rows = trail_rows(&fly(&mut world, 10));
+        assert_eq!(rows[0], 66);
+        assert!((109..=111).contains(rows.last().unwrap()), "trail of 10 points should span 45 m, ends at row {:?}", rows.last());
+        assert_eq!(rows.len() as u32, rows.last().unwrap() - 65, "trail should be a continuous line");
+
+        // Older points are dropped once the trail is full
+        world.trail_length = 4;
+        let rows = trail_rows(&fly(&mut world, 10));
+        assert_eq!(rows[0], 66);
+        assert!((80..=82).contains(rows.last().unwrap()), "trail of 4 points should span 15 m, ends at row {:?}", rows.last());
+
+        world.reset_trail();
+        assert!(trail_rows(&fly(&mut world, 1)).is_empty());
+    }
+
+    #[test]
+    fn world_render_draws_a_trail_per_aircraft() {
+        let mut world = test_world(8);
+        world.add_aircraft(aircraft_at(-40.0, -25.0, -100.0, 0.0));
+        world.add_aircraft(aircraft_at(-40.0, 25.0, -100.0, 0.0));
+        world.trail_length = 6;
+
+        // Both fly north 10 m a frame, 16 pixels to 25 m
+        let mut canvas = world.render();
+        for step in 1..6 {
+            let x = -40.0 + 10.0 * step as f64;
+            world.update_aircraft(aircraft_at(x, -25.0, -100.0, 0.0), 0);
+            world.update_aircraft(aircraft_at(x, 25.0, -100.0, 0.0), 1);
+            canvas = world.render();
+        }
+
+        // North is to the right, so each trail runs left from its aircraft at column 38 to column 6 and fades with age
+        let red = |x: u32, y: u32| {
+            let [r, g, b] = rgb(&canvas, x, y);
+            r as i32 - (g as i32 + b as i32) / 2
+        };
+        for row in [16, 48] {
+            assert!(red(30, row) > 0 && red(10, row) > 0, "no trail in row {}", row);
+            assert!(red(30, row) > red(10, row));
+            assert!(red(50, row) <= 0);
+        }
+        assert!(red(20, 32) <= 0);
+    }
+
+    #[test]
+    fn image_is_packed_rgb_rows_from_top_left() {
+        let mut world = test_world(8);
+        world.screen_dims = Vec2::new(64.0, 48.0);
+        // Tree filling the 16 x 12 pixel tile in the top-left corner of the frame
+        add_tree(&mut world, Vec2::new(-50.0, -50.0), [255, 0, 0], "tree");
+
+        let image = world.get_image();
+        assert_eq!(image.len(), 64 * 48 * 3);
+        let pixel = |x: usize, y: usize| &image[(y * 64 + x) * 3..(y * 64 + x) * 3 + 3];
+        assert_eq!(pixel(0, 0), [255, 0, 0]);
+        assert_eq!(pixel(15, 11), [255, 0, 0]);
+        assert_eq!(pixel(17, 0), [86, 152, 54]);
+        assert_eq!(pixel(0, 13), [86, 152, 54]);
+    }
+
+    // Sum of squared luminance steps between neighbouring pixels, large when edges are hard
+    fn edge_energy(pixmap: &Pixmap) -> f32 {
+        let luminance = |x: u32, y: u32| {
+            let colour = pixmap.pixel(x, y).unwrap().demultiply();
+            0.2126 * colour.red() as f32 + 0.7152 * colour.green() as f32 + 0.0722 * colour.blue() as f32
+        };
+        let mut energy = 0.0;
+        for y in 0..pixmap.height() - 1 {
+            for x in 0..pixmap.width() - 1 {
+                energy += (luminance(x + 1, y) - luminance(x, y)).powf(2.0) + (luminance(x, y + 1) - luminance(x, y)).powf(2.0);
+            }
+        }
+        energy
+    }
+
+    #[test]
+    fn anti_aliasing_softens_edges() {
+        let render = |anti_aliasing: u32| {
+            let mut world = test_world(8);
+            world.anti_aliasing = anti_aliasing;
+            world.set_object_asset("t67h", sprite(255, 0, 0));
+            world.add_aircraft(aircraft_at(0.0, 0.0, -100.0, 0.5));
+            world.render()
+        };
+
+        let aliased = render(1);
+        let smoothed = render(2);
+        assert_eq!((smoothed.width(), smoothed.height()), (aliased.width(), aliased.height()));
+        assert!(edge_energy(&smoothed) < 0.9 * edge_energy(&aliased), "{} vs {}", edge_energy(&smoothed), edge_energy(&aliased));
+
+        // Unsupported factors fall back to the largest supported one rather than allocating a huge frame
+        assert_eq!(render(64).data(), render(4).data());
+    }
+
+    #[test]
+    fn lower_tree_is_drawn_in_front() {
+        let mut world = test_world(8);
+        // Generated front to back, so drawing in generation order would hide the lower tree
+        add_tree(&mut world, Vec2::new(0.0, 8.0), [255, 0, 0], "front");
+        add_tree(&mut world, Vec2::ZERO, [0, 0, 255], "behind");
+
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 36, 34), [0, 0, 255]);
+        assert_eq!(rgb(&canvas, 36, 40), [255, 0, 0]);
+        assert_eq!(rgb(&canvas, 36, 52), [255, 0, 0]);
+    }
+
+    #[test]
+    fn distant_tiles_are_hazier() {
+        let mut world = test_world(40);
+        world.camera.move_camera(vec![0.0, 0.0, -1000.0]);
+        world.fog_density = 0.001;
+        world.fog_colour = [255, 255, 255];
+        let canvas = world.render();
+
+        // Distance from the haze colour, the corner tiles are about 1.2 km away and those below the camera 1 km
+        let clarity = |x: u32, y: u32| rgb(&canvas, x, y).iter().map(|&c| 255 - c as u32).sum::<u32>();
+        let near = clarity(32, 32);
+        let far = clarity(1, 1);
+        let clear = 3 * 255 - (86 + 152 + 54);
+        assert!(far < near && near < clear, "clear {} near {} far {}", clear, near, far);
+    }
+
+    #[test]
+    fn banded_tiles_match_a_single_pass() {
+        // Tiles at fractional positions and scales straddling band edges, with and without haze
+        let tiles: Vec<(Pixmap, Transform, f32)> = (0..60)
+            .map(|i| {
+                let colour = sprite((i * 37 % 256) as u8, (i * 91 % 256) as u8, (i * 53 % 256) as u8);
+                let transform = Transform::from_row(1.37, 0.0, 0.0, 1.37, (i % 10) as f32 * 21.3 - 7.1, (i / 10) as f32 * 21.7 - 5.4);
+                (colour, transform, (i % 3) as f32 * 0.3)
+            })
+            .collect();
+
+        let serial = rasterise_tiles(200, 130, &tiles, [200, 210, 225], 130);
+        for band_height in [1, 7, 64] {
+            let banded = rasterise_tiles(200, 130, &tiles, [200, 210, 225], band_height);
+            assert!(banded.data() == serial.data(), "bands of {} rows differ from a single pass", band_height);
+        }
+    }
+
+    #[test]
+    fn terrain_layer_is_cached() {
+        let mut world = test_world(8);
+        world.set_object_asset("t67h", sprite(255, 0, 0));
+        world.add_aircraft(aircraft_at(0.0, 0.0, -100.0, 0.0));
+
+        // A static camera reuses the layer even as the aircraft moves over it
+        let first = world.render();
+        world.update_aircraft(aircraft_at(10.0, 0.0, -100.0, 0.0), 0);
+        let second = world.render();
+        assert_eq!(world.terrain_renders(), 1);
+        assert!(first.data() != second.data());
+
+        // Anything the layer depends on redraws it
+        world.camera.move_camera(vec![5.0, 0.0, -100.0]);
+        world.render();
+        world.scale = 20.0;
+        world.render();
+        world.set_runway(None);
+        world.render();
+        add_tree(&mut world, Vec2::ZERO, [0, 0, 255], "tree");
+        world.render();
+        assert_eq!(world.terrain_renders(), 5);
+    }
+
+    #[test]
+    fn terrain_is_readable_through_accessors() {
+        let mut world = test_world(2);
+        add_tree(&mut world, Vec2::ZERO, [0, 0, 255], "tree");
+        world.create_runway();
+
+        assert_eq!(world.tiles().len(), 4);
+        assert_eq!(world.objects().len(), 1);
+        assert_eq!(world.tile_map()["grass"].pixel(0, 0), sprite(86, 152, 54).pixel(0, 0));
+        assert_eq!(world.object_map()["tree"].pixel(0, 0), sprite(0, 0, 255).pixel(0, 0));
+        assert_eq!(world.runway().map(|runway| runway.dims), Some(Runway::default().dims));
+    }
+
+    #[test]
+    fn doubling_zoom_halves_screen_distances() {
+        let mut world = test_world(8);
+        world.set_object_asset("t67h", sprite(255, 0, 0));
+        world.add_aircraft(aircraft_at(-20.0, 0.0, -100.0, 0.0));
+        world.add_aircraft(aircraft_at(20.0, 0.0, -100.0, 0.0));
+
+        let (a, b) = (Vec2::new(-20.0, 0.0), Vec2::new(20.0, 0.0));
+        let near = world.world_to_screen(a).distance(world.world_to_screen(b));
+        world.camera.set_zoom(2.0);
+        let far = world.world_to_screen(a).distance(world.world_to_screen(b));
+        assert!((far - near / 2.0).abs() < 1e-4, "{} px at zoom 1, {} px at zoom 2", near, far);
+
+        // The aircraft are drawn where the mapping puts them
+        let canvas = world.render();
+        for point in [a, b] {
+            let pix = world.world_to_screen(point);
+            assert_eq!(rgb(&canvas, pix.x as u32, pix.y as u32), [255, 0, 0]);
+        }
+
+        world.camera.set_zoom(1e6);
+        assert_eq!(world.camera.zoom(), Camera::MAX_ZOOM);
+    }
+
+    #[test]
+    fn camera_follow_settles_without_overshoot() {
+        let follow = |dt: f64, duration: f64| {
+            let mut camera = Camera::default();
+            camera.move_camera(vec![0.0, 0.0, -100.0]);
+            let mut path = vec![camera.x];
+            for _ in 0..(duration / dt).round() as usize {
+                camera.follow(vec![100.0, 0.0, -100.0], dt);
+                path.push(camera.x);
+            }
+            path
+        };
+
+        // Approaches the step monotonically and never passes it
+        let path = follow(1.0 / 60.0, 10.0);
+        assert!(path.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(path.iter().all(|&x| x <= 100.0));
+        assert!((path.last().unwrap() - 100.0).abs() < 1e-3);
+
+        // The same after a second whatever the frame rate
+        let slow = *follow(1.0 / 30.0, 1.0).last().unwrap();
+        let fast = *follow(1.0 / 120.0, 1.0).last().unwrap();
+        assert!((slow - fast).abs() < 1e-9, "{} at 30 Hz, {} at 120 Hz", slow, fast);
+    }
+
+    // Cockpit view from 50 m above the ground, 500 m south of the map centre
+    fn cockpit_world(roll: f64, pitch: f64, yaw: f64) -> World {
+        let mut world = test_world(8);
+        world.render_type = "cockpit".to_string();
+        world.add_aircraft(Aircraft::new(
+            "TO",
+            Vector3::new(-500.0, 0.0, -50.0),
+            Vector3::zeros(),
+            UnitQuaternion::from_euler_angles(roll, pitch, yaw),
+            Vector3::zeros(),
+            None,
+            None
+        ));
+        world
+    }
+
+    // First row from the top of a column that isn't sky
+    fn horizon_row(canvas: &Pixmap, column: u32) -> u32 {
+        (0..canvas.height()).find(|&y| rgb(canvas, column, y) != [110, 160, 220]).unwrap()
+    }
+
+    #[test]
+    fn cockpit_horizon_rises_when_pitching_down() {
+        // Sampled in the gap at the centre of the aircraft symbol
+        let level = horizon_row(&cockpit_world(0.0, 0.0, 0.0).render(), 32);
+        let nose_down = horizon_row(&cockpit_world(0.0, -0.1, 0.0).render(), 32);
+
+        // The horizon is at infinity so it sits on the centre line when level and focal length x tan(pitch) above it
+        let focal_length = 32.0 / (World::COCKPIT_FOV / 2.0).tan();
+        assert_eq!(level, 32);
+        assert!((nose_down as f32 - (32.0 - focal_length * 0.1_f32.tan())).abs() <= 1.0, "horizon at row {}", nose_down);
+
+        // Rolling right tilts it so it is higher on the right of the screen
+        let rolled = cockpit_world(0.3, 0.0, 0.0).render();
+        assert!(horizon_row(&rolled, 40) < horizon_row(&rolled, 24));
+    }
+
+    #[test]
+    fn cockpit_shows_terrain_ahead() {
+        // Runway 500 m north of the aircraft
+        let render = |yaw: f64| {
+            let mut world = cockpit_world(0.0, -0.1, yaw);
+            world.set_object_asset("runway", sprite(255, 255, 255));
+            world.create_runway();
+            world.render()
+        };
+        let runway_pixels = |canvas: &Pixmap| (0..64).filter(|&y| rgb(canvas, 32, y) == [255, 255, 255]).count();
+
+        assert!(runway_pixels(&render(0.0)) > 0);
+        assert_eq!(runway_pixels(&render(std::f64::consts::PI)), 0);
+    }
+
+    #[test]
+    fn cockpit_without_aircraft_shows_the_world() {
+        let mut world = test_world(8);
+        world.render_type = "cockpit".to_string();
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 32, 10), [86, 152, 54]);
+        assert_eq!(rgb(&canvas, 32, 50), [86, 152, 54]);
+    }
+
+    #[test]
+    fn cockpit_draws_objects_ahead() {
+        let mut world = cockpit_world(0.0, 0.0, 0.0);
+        add_tree(&mut world, Vec2::new(-412.5, -12.5), [255, 0, 0], "tree");
+
+        // The tree 100 m ahead stands below the horizon in the middle of the screen, its foot 50 m below eye level
+        let canvas = world.render();
+        assert_eq!(rgb(&canvas, 32, 52), [255, 0, 0]);
+        assert_ne!(rgb(&canvas, 5, 52), [255, 0, 0]);
+    }
+
+    #[test]
+    fn irregular_dt_runs_whole_fixed_steps() {
+        let mut world = test_world(8);
+        world.add_aircraft(aircraft_at(0.0, 0.0, -500.0, 0.0));
+        let mut reference = world.vehicles[0].clone();
+        let fixed_dt = 1.0 / world.settings.simulation_frequency;
+
+        let frame_times = [0.013, 0.051, 0.0, 0.2, 0.007, 0.033, 0.1666, 0.0021, 0.09, 0.25];
+        let steps: usize = frame_times.iter().map(|&dt| world.step(dt)).sum();
+
+        // Only whole steps are simulated, the remainder waits for the next call
+        let elapsed: f64 = frame_times.iter().sum();
+        assert_eq!(steps, (elapsed / fixed_dt).floor() as usize);
+        assert!((world.sim_time - steps as f64 * fixed_dt).abs() < 1e-9);
+        assert!(elapsed - world.sim_time < fixed_dt);
+
+        // The aircraft ends up where the same number of fixed steps puts it
+        for _ in 0..steps {
+            reference.step(fixed_dt);
+        }
+        assert_eq!(world.vehicles[0].position(), reference.position());
+    }
+}